@@ -1,7 +1,5 @@
 use q_parser::parsetree::*;
-use q_parser::token::*;
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -9,20 +7,29 @@ pub enum EnvironmentError {
     #[error("The symbol {id:?} has not been defined in the environment.")]
     UndefinedSymbol { id: Id },
 
+    #[error("The symbol {id:?} can't be assigned to because it was not declared with `mut`.")]
+    AssignToImmutable { id: Id },
+
     #[error("Attempted to pop one scope too many")]
     ScopeUnderflow,
 }
 
+#[derive(Clone, Debug)]
+struct Binding {
+    value: Expression,
+    mutable: bool,
+}
+
 #[derive(Default, Clone, Debug)]
 pub struct Scope {
     parent: Option<Box<Scope>>,
-    bindings: HashMap<Id, Expression>,
+    bindings: HashMap<Id, Binding>,
 }
 
 impl Scope {
     pub fn lookup(&self, id: Id) -> Result<Expression, EnvironmentError> {
-        if let Some(expr) = self.bindings.get(&id) {
-            Ok((*expr).clone())
+        if let Some(binding) = self.bindings.get(&id) {
+            Ok(binding.value.clone())
         } else {
             if let Some(parent) = &self.parent {
                 return parent.lookup(id);
@@ -30,9 +37,31 @@ impl Scope {
             Err(EnvironmentError::UndefinedSymbol { id })
         }
     }
+
+    pub fn is_bound(&self, id: &Id) -> bool {
+        self.bindings.contains_key(id)
+            || self
+                .parent
+                .as_ref()
+                .map_or(false, |parent| parent.is_bound(id))
+    }
+
+    pub fn assign(&mut self, id: Id, expr: Expression) -> Result<(), EnvironmentError> {
+        match self.bindings.get_mut(&id) {
+            Some(binding) if binding.mutable => {
+                binding.value = expr;
+                Ok(())
+            }
+            Some(_) => Err(EnvironmentError::AssignToImmutable { id }),
+            None => match &mut self.parent {
+                Some(parent) => parent.assign(id, expr),
+                None => Err(EnvironmentError::UndefinedSymbol { id }),
+            },
+        }
+    }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Environment {
     current_scope: Scope,
 }
@@ -67,14 +96,57 @@ impl Environment {
     }
 
     pub fn bind(&mut self, id: Id, expr: Expression) {
-        self.current_scope.bindings.insert(id, expr);
+        let binding = Binding {
+            value: expr,
+            mutable: false,
+        };
+        self.current_scope.bindings.insert(id, binding);
+    }
+
+    /// Binds a name that can later be updated in place with `assign`.
+    pub fn bind_mut(&mut self, id: Id, expr: Expression) {
+        let binding = Binding {
+            value: expr,
+            mutable: true,
+        };
+        self.current_scope.bindings.insert(id, binding);
+    }
+
+    /// Updates the closest binding of `id`, which must have been made with
+    /// `bind_mut`, instead of shadowing it.
+    pub fn assign(&mut self, id: Id, expr: Expression) -> Result<(), EnvironmentError> {
+        self.current_scope.assign(id, expr)
     }
 
     pub fn lookup(&self, id: Id) -> Result<Expression, EnvironmentError> {
         self.current_scope.lookup(id)
     }
+
+    /// Whether `lookup` would find `id`.
+    pub fn is_bound(&self, id: &Id) -> bool {
+        self.current_scope.is_bound(id)
+    }
+
+    /// The bindings of every scope but the outermost one, which holds the
+    /// values of the module, sorted by name. Inner bindings shadow outer ones.
+    pub fn locals(&self) -> Vec<(Id, Expression)> {
+        let mut seen = HashSet::new();
+        let mut locals = vec![];
+        let mut scope = &self.current_scope;
+        while let Some(parent) = &scope.parent {
+            for (id, binding) in &scope.bindings {
+                if seen.insert(id) {
+                    locals.push((id.clone(), binding.value.clone()));
+                }
+            }
+            scope = parent;
+        }
+        locals.sort_by(|(a, _), (b, _)| a.0.cmp(&b.0));
+        locals
+    }
 }
 
+#[cfg(test)]
 mod tests {
     use super::*;
 
@@ -82,7 +154,7 @@ mod tests {
     fn env_can_lookup_on_parent_scope() {
         let mut env = Environment::new();
         let a = Id("a".to_string());
-        let first_str = Expression::LiteralString("hello".to_string());
+        let first_str: Expression = ExpressionKind::LiteralString("hello".to_string()).into();
         env.bind(a.clone(), first_str.clone());
         assert_eq!(env.lookup(a.clone()).unwrap(), first_str);
         env.push_scope();
@@ -94,7 +166,7 @@ mod tests {
         let mut env = Environment::new();
         env.push_scope();
         let a = Id("a".to_string());
-        let first_str = Expression::LiteralString("hello".to_string());
+        let first_str: Expression = ExpressionKind::LiteralString("hello".to_string()).into();
         env.bind(a.clone(), first_str.clone());
         assert!(matches!(env.lookup(a.clone()), Ok(expr) if expr == first_str));
         env.pop_scope().unwrap();
@@ -107,15 +179,63 @@ mod tests {
     fn environment_variable_shadowing() {
         let mut env = Environment::new();
         let a = Id("a".to_string());
-        let first_str = Expression::LiteralString("hello".to_string());
+        let first_str: Expression = ExpressionKind::LiteralString("hello".to_string()).into();
         env.bind(a.clone(), first_str.clone());
         assert_eq!(env.lookup(a).unwrap(), first_str);
 
         env.push_scope();
 
         let a = Id("a".to_string());
-        let second_str = Expression::LiteralString("goodbye".to_string());
+        let second_str: Expression = ExpressionKind::LiteralString("goodbye".to_string()).into();
         env.bind(a.clone(), second_str.clone());
         assert_eq!(env.lookup(a).unwrap(), second_str);
     }
+
+    #[test]
+    fn environment_assigns_in_place() {
+        let mut env = Environment::new();
+        let a = Id("a".to_string());
+        let first: Expression = ExpressionKind::Integer(1).into();
+        let second: Expression = ExpressionKind::Integer(2).into();
+        env.bind_mut(a.clone(), first);
+
+        env.push_scope();
+        env.assign(a.clone(), second.clone()).unwrap();
+        env.pop_scope().unwrap();
+
+        assert_eq!(env.lookup(a).unwrap(), second);
+    }
+
+    #[test]
+    fn environment_lists_the_bindings_of_inner_scopes() {
+        let mut env = Environment::new();
+        let id = |name: &str| Id(name.to_string());
+        let int = |int| -> Expression { ExpressionKind::Integer(int).into() };
+        env.bind(id("global"), int(0));
+        env.push_scope();
+        env.bind(id("b"), int(1));
+        env.bind(id("a"), int(2));
+        env.push_scope();
+        env.bind(id("b"), int(3));
+
+        assert_eq!(env.locals(), vec![(id("a"), int(2)), (id("b"), int(3))]);
+    }
+
+    #[test]
+    fn environment_can_not_assign_to_immutable_bindings() {
+        let mut env = Environment::new();
+        let a = Id("a".to_string());
+        let b = Id("b".to_string());
+        let value: Expression = ExpressionKind::Integer(1).into();
+        env.bind(a.clone(), value.clone());
+
+        assert!(matches!(
+            env.assign(a.clone(), value.clone()),
+            Err(EnvironmentError::AssignToImmutable { id }) if id == a
+        ));
+        assert!(matches!(
+            env.assign(b.clone(), value),
+            Err(EnvironmentError::UndefinedSymbol { id }) if id == b
+        ));
+    }
 }
@@ -1,125 +1,1062 @@
 use crate::environment::*;
+use miette::{Diagnostic, SourceSpan};
+use q_parser::loader::ModuleGraph;
 use q_parser::parsetree::*;
-use q_parser::token::*;
+use std::collections::HashMap;
 use thiserror::Error;
 
-#[derive(Error, Debug)]
+#[derive(Error, Diagnostic, Debug)]
 pub enum InterpreterError {
-    #[error("We were expecting a {expected:?}, but instead found: {found:?}")]
-    UnexpectedSymbolFound { expected: Token, found: Token },
+    #[error("We expected a function but instead found {:#?}", .expr.kind)]
+    CannotCallNonFunctionValue {
+        expr: Expression,
+        #[label("this is not a function")]
+        span: SourceSpan,
+    },
 
-    #[error("We were expecting an expression, but instead found: {found:?}")]
-    ExpectedExpression { found: Token },
+    #[error("The operator {op:?} can't be used with {:?} and {:?}", .lhs.kind, .rhs.kind)]
+    InvalidOperands {
+        op: BinaryOp,
+        lhs: Expression,
+        rhs: Expression,
+        #[label("invalid operands")]
+        span: SourceSpan,
+    },
 
-    #[error("We expected {id:?} to be a function but instead found {expr:#?}")]
-    CannotCallNonFunctionValue { id: Id, expr: Expression },
+    #[error("The operator {op:?} can't be used with {:?}", .expr.kind)]
+    InvalidOperand {
+        op: UnaryOp,
+        expr: Expression,
+        #[label("invalid operand")]
+        span: SourceSpan,
+    },
+
+    #[error("This arithmetic operation overflowed")]
+    ArithmeticOverflow {
+        #[label("this overflowed")]
+        span: SourceSpan,
+    },
+
+    #[error("We tried to divide by zero")]
+    DivisionByZero {
+        #[label("this divides by zero")]
+        span: SourceSpan,
+    },
 
     #[error("We could not find a clause that matches these arguments")]
-    ClauseMatchError,
+    ClauseMatchError {
+        #[label("no clause matches this call")]
+        span: SourceSpan,
+    },
+
+    #[error("No arm of this match expression matches {:?}", .value.kind)]
+    MatchError {
+        value: Expression,
+        #[label("no arm matches this value")]
+        span: SourceSpan,
+    },
+
+    #[error("A guard must be true or false, but this one is {:?}", .value.kind)]
+    GuardNotABool {
+        value: Expression,
+        #[label("this guard is not a boolean")]
+        span: SourceSpan,
+    },
+
+    #[error("There is no struct named {name:?}")]
+    UnknownStruct {
+        name: Id,
+        #[label("unknown struct")]
+        span: SourceSpan,
+    },
+
+    #[error("The struct {name:?} has no field {field:?}")]
+    UnknownField {
+        name: Id,
+        field: Id,
+        #[label("unknown field")]
+        span: SourceSpan,
+    },
+
+    #[error("The field {field:?} of {name:?} is missing")]
+    MissingField {
+        name: Id,
+        field: Id,
+        #[label("missing field {field:?}")]
+        span: SourceSpan,
+    },
+
+    #[error("We expected a struct but instead found {:?}", .value.kind)]
+    NotAStruct {
+        value: Expression,
+        #[label("this is not a struct")]
+        span: SourceSpan,
+    },
+
+    #[error("{name:?} takes {expected} arguments but was given {found}")]
+    WrongNumberOfArguments {
+        name: Id,
+        expected: usize,
+        found: usize,
+        #[label("wrong number of arguments")]
+        span: SourceSpan,
+    },
+
+    #[error("The interpreter can't run processes yet")]
+    NoProcesses {
+        #[label("this needs an actor runtime")]
+        span: SourceSpan,
+    },
+
+    #[error("We could not find the module that declares {id}")]
+    UnknownModule {
+        id: RemoteId,
+        #[label("unknown module")]
+        span: SourceSpan,
+    },
+
+    #[error("{error}")]
+    EnvironmentError {
+        error: EnvironmentError,
+        #[label]
+        span: SourceSpan,
+    },
+}
+
+impl q_core::diagnostic::Diagnostic for InterpreterError {}
 
-    #[error(transparent)]
-    EnvironmentError(EnvironmentError),
+/// An error that stopped a program, along with the path of the module whose
+/// code raised it, which is the source its span points into.
+#[derive(Debug)]
+pub struct RuntimeError {
+    pub module: Vec<Id>,
+    pub error: InterpreterError,
+}
+
+impl InterpreterError {
+    fn environment(span: Span) -> impl FnOnce(EnvironmentError) -> Self {
+        move |error| InterpreterError::EnvironmentError {
+            error,
+            span: span.into(),
+        }
+    }
 }
 
 pub struct Interpreter {
-    program: Module,
     env: Environment,
+    /// The path of the module whose code is running.
+    module: Vec<Id>,
+    /// The module-level values of every loaded module, by the full path that
+    /// name resolution gives remote names.
+    modules: HashMap<Vec<Id>, Environment>,
+    /// The types of every loaded module, by the same paths. Unqualified type
+    /// names only ever refer to the types of the running module.
+    types: HashMap<Vec<Id>, Types>,
+    /// The module whose code raised the error on its way up, once the
+    /// function body it was raised in has returned it.
+    error_module: Option<Vec<Id>>,
+}
+
+/// The structs and enum variants one module declares.
+#[derive(Default)]
+struct Types {
+    /// The field names of every struct, in declaration order.
+    structs: HashMap<Id, Vec<Id>>,
+    /// How many arguments every enum variant takes.
+    variants: HashMap<Id, usize>,
 }
 
 impl Interpreter {
+    /// An interpreter for a single module that doesn't use any others.
+    #[cfg(test)]
     pub fn new(program: Module) -> Self {
-        let mut env = Environment::new();
+        let path = vec![program.name.clone()];
+        Self::with_modules(path.clone(), [(path, &program)])
+    }
+
+    /// An interpreter for a whole program, running in its entry module. The
+    /// graph must have gone through name resolution.
+    pub fn from_graph(graph: &ModuleGraph) -> Self {
+        let entry = graph.get(graph.entry()).path.clone();
+        let modules = graph
+            .iter()
+            .map(|(_, loaded)| (loaded.path.clone(), &loaded.module));
+        Self::with_modules(entry, modules)
+    }
 
-        for item in &program.items {
-            if let ModuleItem::ValueDeclaration(vd) = item {
-                env.bind(vd.name.clone(), vd.value.clone())
+    /// An interpreter running in the module at `entry`, which must be one of
+    /// `modules`.
+    fn with_modules<'a>(
+        entry: Vec<Id>,
+        modules: impl IntoIterator<Item = (Vec<Id>, &'a Module)>,
+    ) -> Self {
+        let mut interpreter = Self {
+            env: Environment::new(),
+            module: entry,
+            modules: HashMap::new(),
+            types: HashMap::new(),
+            error_module: None,
+        };
+        for (path, module) in modules {
+            let env = interpreter.declare(&path, module);
+            interpreter.modules.insert(path, env);
+        }
+        interpreter.env = interpreter.modules[&interpreter.module].clone();
+        interpreter
+    }
+
+    /// Registers the types the module at `path` declares and returns its
+    /// values. Its functions become closures over the module.
+    fn declare(&mut self, path: &[Id], module: &Module) -> Environment {
+        let mut env = Environment::new();
+        let types = self.types.entry(path.to_vec()).or_default();
+        for item in &module.items {
+            match item {
+                ModuleItem::ValueDeclaration(vd) => {
+                    let value = match &vd.value.kind {
+                        ExpressionKind::Function(clauses) => Expression::new(
+                            ExpressionKind::Closure {
+                                clauses: clauses.clone(),
+                                captured: vec![],
+                                module: path.to_vec(),
+                            },
+                            vd.value.span,
+                        ),
+                        _ => vd.value.clone(),
+                    };
+                    env.bind(vd.name.clone(), value);
+                }
+                ModuleItem::StructDeclaration(decl) => {
+                    let fields = decl.fields.iter().map(|field| field.name.clone()).collect();
+                    types.structs.insert(decl.name.clone(), fields);
+                }
+                ModuleItem::EnumDeclaration(decl) => {
+                    for variant in &decl.variants {
+                        types
+                            .variants
+                            .insert(variant.name.clone(), variant.args.len());
+                    }
+                }
+                // Submodules are separate files that the module loader takes
+                // care of.
+                ModuleItem::ModDeclaration(_) => {}
             }
         }
+        env
+    }
 
-        Self { program, env }
+    pub fn main(mut self) -> Result<(), RuntimeError> {
+        let result = self.eval(
+            ExpressionKind::Call {
+                callee: Box::new(ExpressionKind::Variable(Id("main".to_string())).into()),
+                args: vec![ExpressionKind::LiteralString("hello world".to_string()).into()],
+            }
+            .into(),
+        );
+        // the call of `main` itself is written in the entry module
+        self.blame(result)
+            .map(|_| ())
+            .map_err(|error| RuntimeError {
+                module: self
+                    .error_module
+                    .take()
+                    .expect("errors are blamed on a module"),
+                error,
+            })
     }
 
-    pub fn main(mut self) -> Result<(), InterpreterError> {
-        self.eval(Expression::Call {
-            id: Id("main".to_string()),
-            args: vec![Expression::LiteralString("hello world".to_string())],
-        })
-        .map(|_| ())
+    /// Remembers that an error in `result` was raised by the code of the
+    /// running module, unless a function it called already raised it.
+    fn blame<T>(&mut self, result: Result<T, InterpreterError>) -> Result<T, InterpreterError> {
+        if result.is_err() && self.error_module.is_none() {
+            self.error_module = Some(self.module.clone());
+        }
+        result
     }
 
     pub fn eval(&mut self, expr: Expression) -> Result<Expression, InterpreterError> {
-        match expr {
-            Expression::Call { id, args } if id == Id("print".to_string()) => {
-                let mut args_exprs = vec![];
-                for arg in args {
-                    args_exprs.push(self.eval(arg)?);
-                }
-                print!("{:?}", args_exprs);
-                Ok(Expression::LiteralString("ok".to_string()))
-            }
-            Expression::Call { id, args } => {
-                match self
-                    .env
-                    .lookup(id.clone())
-                    .map_err(InterpreterError::EnvironmentError)?
-                {
-                    Expression::Function(clauses) => self.eval_function(clauses, args),
-                    expr => Err(InterpreterError::CannotCallNonFunctionValue { id, expr }),
+        let span = expr.span;
+        let value = |kind| Ok(Expression::new(kind, span));
+        match expr.kind {
+            ExpressionKind::Call { callee, args } => self.eval_call(*callee, args, span),
+            ExpressionKind::MethodCall {
+                receiver,
+                method,
+                args,
+            } => {
+                let receiver = self.eval(*receiver)?;
+                let owner = match &receiver.kind {
+                    ExpressionKind::Struct { module, .. }
+                    | ExpressionKind::Variant { module, .. } => Some(module.clone()),
+                    _ => None,
+                }
+                .filter(|path| self.modules[path].is_bound(&method));
+
+                let mut values = vec![receiver];
+                values.extend(self.eval_all(args)?);
+                let callee = match &owner {
+                    Some(path) => self.modules[path].lookup(method),
+                    None => self.env.lookup(method),
+                }
+                .map_err(InterpreterError::environment(span))?;
+                self.call(callee, values, span)
+            }
+            ExpressionKind::Variable(id) if self.local_variant(&id) == Some(0) => {
+                value(ExpressionKind::Variant {
+                    name: id,
+                    args: vec![],
+                    module: self.module.clone(),
+                })
+            }
+            ExpressionKind::Remote(id) if self.variant(&id.path, &id.name) == Some(0) => {
+                value(ExpressionKind::Variant {
+                    name: id.name,
+                    args: vec![],
+                    module: id.path,
+                })
+            }
+            ExpressionKind::Remote(id) => self.lookup_remote(&id, span),
+            ExpressionKind::Function(clauses) => value(ExpressionKind::Closure {
+                clauses,
+                captured: self.env.locals(),
+                module: self.module.clone(),
+            }),
+            ExpressionKind::Struct { name, fields, .. } => {
+                let fields = self.eval_fields(fields)?;
+                let module = self.module.clone();
+                let struct_fields = self.struct_fields(&module, &name, &fields, span)?;
+
+                let mut values = vec![];
+                for field in struct_fields {
+                    let Some((_, value)) = fields.iter().find(|(name, _)| *name == field) else {
+                        return Err(InterpreterError::MissingField {
+                            name,
+                            field,
+                            span: span.into(),
+                        });
+                    };
+                    values.push((field, value.clone()));
+                }
+                value(ExpressionKind::Struct {
+                    name,
+                    fields: values,
+                    module,
+                })
+            }
+            ExpressionKind::StructUpdate { expr, fields } => {
+                let updated = self.eval(*expr)?;
+                let updates = self.eval_fields(fields)?;
+                let ExpressionKind::Struct {
+                    name,
+                    fields,
+                    module,
+                } = updated.kind
+                else {
+                    return Err(InterpreterError::NotAStruct {
+                        value: updated,
+                        span: span.into(),
+                    });
+                };
+                self.struct_fields(&module, &name, &updates, span)?;
+
+                let fields = fields
+                    .into_iter()
+                    .map(
+                        |(field, value)| match updates.iter().find(|(name, _)| *name == field) {
+                            Some((_, update)) => (field, update.clone()),
+                            None => (field, value),
+                        },
+                    )
+                    .collect();
+                value(ExpressionKind::Struct {
+                    name,
+                    fields,
+                    module,
+                })
+            }
+            ExpressionKind::FieldAccess { expr, field } => {
+                let value = self.eval(*expr)?;
+                let ExpressionKind::Struct { name, fields, .. } = value.kind else {
+                    return Err(InterpreterError::NotAStruct {
+                        value,
+                        span: span.into(),
+                    });
+                };
+                match fields.into_iter().find(|(name, _)| *name == field) {
+                    Some((_, value)) => Ok(value),
+                    None => Err(InterpreterError::UnknownField {
+                        name,
+                        field,
+                        span: span.into(),
+                    }),
+                }
+            }
+            ExpressionKind::Tuple(exprs) => value(ExpressionKind::Tuple(self.eval_all(exprs)?)),
+            ExpressionKind::List(exprs) => value(ExpressionKind::List(self.eval_all(exprs)?)),
+            ExpressionKind::InterpolatedString(parts) => {
+                let mut str = String::new();
+                for part in parts {
+                    match part {
+                        StringPart::Literal(literal) => str.push_str(&literal),
+                        StringPart::Expression(expr) => {
+                            let value = self.eval(expr)?;
+                            str.push_str(&Self::to_display_string(&value));
+                        }
+                    }
+                }
+                value(ExpressionKind::LiteralString(str))
+            }
+            ExpressionKind::Block(statements) => {
+                let result = self.in_scope(vec![], span, |interpreter| {
+                    let mut result = None;
+                    for statement in statements {
+                        result = Some(interpreter.eval(statement)?);
+                    }
+                    Ok(result)
+                })?;
+                // the parser never produces empty blocks
+                Ok(result.expect("blocks have at least one statement"))
+            }
+            ExpressionKind::Let {
+                name,
+                value,
+                mutable,
+            } => {
+                let value = self.eval(*value)?;
+                if mutable {
+                    self.env.bind_mut(name, value.clone());
+                } else {
+                    self.env.bind(name, value.clone());
+                }
+                Ok(value)
+            }
+            ExpressionKind::Assign { name, value } => {
+                let value = self.eval(*value)?;
+                self.env
+                    .assign(name, value.clone())
+                    .map_err(InterpreterError::environment(span))?;
+                Ok(value)
+            }
+            ExpressionKind::Match { expr, arms } => self.eval_match(*expr, arms, span),
+            ExpressionKind::BinaryOp { op, lhs, rhs } => self
+                .eval_binary_op(op, *lhs, *rhs, span)
+                .map(|kind| Expression::new(kind, span)),
+            ExpressionKind::UnaryOp { op, expr } => {
+                let expr = self.eval(*expr)?;
+                match (op, expr.kind) {
+                    (UnaryOp::Not, ExpressionKind::Bool(bool)) => {
+                        value(ExpressionKind::Bool(!bool))
+                    }
+                    (UnaryOp::Neg, ExpressionKind::Integer(int)) => match int.checked_neg() {
+                        Some(int) => value(ExpressionKind::Integer(int)),
+                        None => Err(InterpreterError::ArithmeticOverflow { span: span.into() }),
+                    },
+                    (UnaryOp::Neg, ExpressionKind::Float(float)) => {
+                        value(ExpressionKind::Float(-float))
+                    }
+                    (op, kind) => Err(InterpreterError::InvalidOperand {
+                        op,
+                        expr: Expression::new(kind, expr.span),
+                        span: span.into(),
+                    }),
                 }
             }
-            Expression::Variable(id) => self
+            ExpressionKind::Variable(id) => self
                 .env
                 .lookup(id)
-                .map_err(InterpreterError::EnvironmentError),
-            _ => Ok(expr),
+                .map_err(InterpreterError::environment(span)),
+            ExpressionKind::Spawn(_)
+            | ExpressionKind::Receive { .. }
+            | ExpressionKind::Send { .. } => {
+                Err(InterpreterError::NoProcesses { span: span.into() })
+            }
+            kind => value(kind),
         }
     }
 
-    fn eval_function(
+    fn eval_all(&mut self, exprs: Vec<Expression>) -> Result<Vec<Expression>, InterpreterError> {
+        exprs.into_iter().map(|expr| self.eval(expr)).collect()
+    }
+
+    fn eval_fields(
         &mut self,
-        clauses: Vec<FunClause>,
+        fields: Vec<(Id, Expression)>,
+    ) -> Result<Vec<(Id, Expression)>, InterpreterError> {
+        fields
+            .into_iter()
+            .map(|(field, expr)| Ok((field, self.eval(expr)?)))
+            .collect()
+    }
+
+    /// How many arguments the variant `name` of the module at `path` takes,
+    /// if the module declares it.
+    fn variant(&self, path: &[Id], name: &Id) -> Option<usize> {
+        self.types.get(path)?.variants.get(name).copied()
+    }
+
+    /// How many arguments the variant `name` of the running module takes, if
+    /// `name` refers to one: local values of the same name take precedence.
+    fn local_variant(&self, name: &Id) -> Option<usize> {
+        if self.env.is_bound(name) {
+            return None;
+        }
+        self.variant(&self.module, name)
+    }
+
+    /// The declared fields of the struct `name` of the module at `path`,
+    /// after checking that every one of `fields` is among them.
+    fn struct_fields(
+        &self,
+        path: &[Id],
+        name: &Id,
+        fields: &[(Id, Expression)],
+        span: Span,
+    ) -> Result<Vec<Id>, InterpreterError> {
+        let struct_fields = self
+            .types
+            .get(path)
+            .and_then(|types| types.structs.get(name));
+        let Some(struct_fields) = struct_fields else {
+            return Err(InterpreterError::UnknownStruct {
+                name: name.clone(),
+                span: span.into(),
+            });
+        };
+
+        for (field, value) in fields {
+            if !struct_fields.contains(field) {
+                return Err(InterpreterError::UnknownField {
+                    name: name.clone(),
+                    field: field.clone(),
+                    span: value.span.into(),
+                });
+            }
+        }
+
+        Ok(struct_fields.clone())
+    }
+
+    fn eval_match(
+        &mut self,
+        expr: Expression,
+        arms: Vec<MatchArm>,
+        span: Span,
+    ) -> Result<Expression, InterpreterError> {
+        let value = self.eval(expr)?;
+
+        for arm in arms {
+            let Some(bindings) = self.match_patterns(
+                std::slice::from_ref(&arm.pattern),
+                std::slice::from_ref(&value),
+            ) else {
+                continue;
+            };
+
+            let result = self.in_scope(bindings, span, |interpreter| {
+                if !interpreter.guard_passes(arm.guard)? {
+                    return Ok(None);
+                }
+                interpreter.eval(arm.body).map(Some)
+            })?;
+            if let Some(result) = result {
+                return Ok(result);
+            }
+        }
+
+        Err(InterpreterError::MatchError {
+            value,
+            span: span.into(),
+        })
+    }
+
+    /// The bindings the patterns introduce when every one of them matches
+    /// its value, or `None` if any of them doesn't. Nothing is bound until
+    /// the whole list of patterns is known to match.
+    fn match_patterns(
+        &self,
+        patterns: &[Pattern],
+        values: &[Expression],
+    ) -> Option<Vec<(Id, Expression)>> {
+        if patterns.len() != values.len() {
+            return None;
+        }
+
+        let mut bindings = vec![];
+        for (pattern, value) in patterns.iter().zip(values) {
+            if !self.collect_bindings(pattern, value, &mut bindings) {
+                return None;
+            }
+        }
+        Some(bindings)
+    }
+
+    fn collect_bindings(
+        &self,
+        pattern: &Pattern,
+        value: &Expression,
+        bindings: &mut Vec<(Id, Expression)>,
+    ) -> bool {
+        match &pattern.kind {
+            PatternKind::Wildcard => true,
+            // a bare variant name like `Zero` matches that variant
+            PatternKind::Bind(id) if self.variant(&self.module, id) == Some(0) => matches!(
+                &value.kind,
+                ExpressionKind::Variant { name, args, module }
+                    if name == id && args.is_empty() && *module == self.module
+            ),
+            PatternKind::Bind(id) => {
+                bindings.push((id.clone(), value.clone()));
+                true
+            }
+            PatternKind::As { pattern, alias } => {
+                if !self.collect_bindings(pattern, value, bindings) {
+                    return false;
+                }
+                bindings.push((alias.clone(), value.clone()));
+                true
+            }
+            PatternKind::Literal(literal) => Self::literal_matches(literal, &value.kind),
+            PatternKind::Tuple(patterns) => match &value.kind {
+                ExpressionKind::Unit => patterns.is_empty(),
+                ExpressionKind::Tuple(values) if patterns.len() == values.len() => patterns
+                    .iter()
+                    .zip(values)
+                    .all(|(pattern, value)| self.collect_bindings(pattern, value, bindings)),
+                _ => false,
+            },
+            PatternKind::Nil => {
+                matches!(&value.kind, ExpressionKind::List(values) if values.is_empty())
+            }
+            PatternKind::Cons { head, tail } => match &value.kind {
+                ExpressionKind::List(values) if !values.is_empty() => {
+                    let rest =
+                        Expression::new(ExpressionKind::List(values[1..].to_vec()), value.span);
+                    self.collect_bindings(head, &values[0], bindings)
+                        && self.collect_bindings(tail, &rest, bindings)
+                }
+                _ => false,
+            },
+            PatternKind::Struct {
+                module,
+                name,
+                fields,
+            } => {
+                match &value.kind {
+                    ExpressionKind::Struct {
+                        name: value_name,
+                        fields: values,
+                        module: value_module,
+                    } if name == value_name
+                        && value_module.as_slice() == self.pattern_module(module) =>
+                    {
+                        fields.iter().all(|(field, pattern)| {
+                            values.iter().find(|(name, _)| name == field).is_some_and(
+                                |(_, value)| self.collect_bindings(pattern, value, bindings),
+                            )
+                        })
+                    }
+                    _ => false,
+                }
+            }
+            PatternKind::Enum { module, name, args } => match &value.kind {
+                ExpressionKind::Variant {
+                    name: value_name,
+                    args: values,
+                    module: value_module,
+                } if name == value_name
+                    && value_module.as_slice() == self.pattern_module(module)
+                    && args.len() == values.len() =>
+                {
+                    args.iter()
+                        .zip(values)
+                        .all(|(pattern, value)| self.collect_bindings(pattern, value, bindings))
+                }
+                _ => false,
+            },
+        }
+    }
+
+    /// The module a variant or struct pattern refers to, which is the running
+    /// one unless the pattern names another.
+    fn pattern_module<'a>(&'a self, module: &'a [Id]) -> &'a [Id] {
+        if module.is_empty() {
+            &self.module
+        } else {
+            module
+        }
+    }
+
+    fn literal_matches(literal: &Literal, value: &ExpressionKind) -> bool {
+        match (literal, value) {
+            (Literal::Bool(lhs), ExpressionKind::Bool(rhs)) => lhs == rhs,
+            (Literal::Atom(lhs), ExpressionKind::Atom(rhs)) => lhs == rhs,
+            (Literal::Integer(lhs), ExpressionKind::Integer(rhs)) => lhs == rhs,
+            (Literal::Float(lhs), ExpressionKind::Float(rhs)) => lhs == rhs,
+            (Literal::Duration(lhs), ExpressionKind::Duration(rhs)) => lhs == rhs,
+            (Literal::String(lhs), ExpressionKind::LiteralString(rhs)) => lhs == rhs,
+            _ => false,
+        }
+    }
+
+    fn eval_binary_op(
+        &mut self,
+        op: BinaryOp,
+        lhs: Expression,
+        rhs: Expression,
+        span: Span,
+    ) -> Result<ExpressionKind, InterpreterError> {
+        let lhs = self.eval(lhs)?;
+
+        // `&&` and `||` only evaluate their right-hand side when they need to
+        match (op, &lhs.kind) {
+            (BinaryOp::And, ExpressionKind::Bool(false)) => return Ok(lhs.kind),
+            (BinaryOp::Or, ExpressionKind::Bool(true)) => return Ok(lhs.kind),
+            _ => (),
+        }
+
+        let rhs = self.eval(rhs)?;
+        let invalid_operands = |lhs, rhs| InterpreterError::InvalidOperands {
+            op,
+            lhs,
+            rhs,
+            span: span.into(),
+        };
+        let overflow = || InterpreterError::ArithmeticOverflow { span: span.into() };
+
+        match (op, &lhs.kind, &rhs.kind) {
+            (BinaryOp::Equal, _, _) => Ok(ExpressionKind::Bool(same_value(&lhs, &rhs))),
+            (BinaryOp::NotEqual, _, _) => Ok(ExpressionKind::Bool(!same_value(&lhs, &rhs))),
+            (BinaryOp::And | BinaryOp::Or, ExpressionKind::Bool(_), ExpressionKind::Bool(_)) => {
+                Ok(rhs.kind)
+            }
+            (
+                BinaryOp::LessThan
+                | BinaryOp::GreaterThan
+                | BinaryOp::LessEqual
+                | BinaryOp::GreaterEqual,
+                lhs_kind,
+                rhs_kind,
+            ) => match Self::ordering(lhs_kind, rhs_kind) {
+                Some(ordering) => Ok(ExpressionKind::Bool(Self::compare(op, ordering))),
+                None => Err(invalid_operands(lhs, rhs)),
+            },
+            (BinaryOp::Add, ExpressionKind::LiteralString(l), ExpressionKind::LiteralString(r)) => {
+                Ok(ExpressionKind::LiteralString(l.clone() + r))
+            }
+            (
+                BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem,
+                ExpressionKind::Integer(l),
+                ExpressionKind::Integer(r),
+            ) => Self::eval_integer_op(op, *l, *r, span).map(ExpressionKind::Integer),
+            (
+                BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem,
+                ExpressionKind::Float(l),
+                ExpressionKind::Float(r),
+            ) => Ok(ExpressionKind::Float(match op {
+                BinaryOp::Add => l + r,
+                BinaryOp::Sub => l - r,
+                BinaryOp::Mul => l * r,
+                BinaryOp::Div => l / r,
+                _ => l % r,
+            })),
+            (BinaryOp::Add, ExpressionKind::Duration(l), ExpressionKind::Duration(r)) => l
+                .checked_add(*r)
+                .map(ExpressionKind::Duration)
+                .ok_or_else(overflow),
+            (BinaryOp::Sub, ExpressionKind::Duration(l), ExpressionKind::Duration(r)) => l
+                .checked_sub(*r)
+                .map(ExpressionKind::Duration)
+                .ok_or_else(overflow),
+            _ => Err(invalid_operands(lhs, rhs)),
+        }
+    }
+
+    fn eval_integer_op(
+        op: BinaryOp,
+        lhs: i64,
+        rhs: i64,
+        span: Span,
+    ) -> Result<i64, InterpreterError> {
+        let result = match op {
+            BinaryOp::Add => lhs.checked_add(rhs),
+            BinaryOp::Sub => lhs.checked_sub(rhs),
+            BinaryOp::Mul => lhs.checked_mul(rhs),
+            BinaryOp::Div | BinaryOp::Rem if rhs == 0 => {
+                return Err(InterpreterError::DivisionByZero { span: span.into() })
+            }
+            BinaryOp::Div => lhs.checked_div(rhs),
+            BinaryOp::Rem => lhs.checked_rem(rhs),
+            _ => unreachable!("{:?} is not an arithmetic operator", op),
+        };
+        result.ok_or(InterpreterError::ArithmeticOverflow { span: span.into() })
+    }
+
+    /// How two values of the same primitive type compare, if they can be.
+    fn ordering(lhs: &ExpressionKind, rhs: &ExpressionKind) -> Option<std::cmp::Ordering> {
+        match (lhs, rhs) {
+            (ExpressionKind::Integer(lhs), ExpressionKind::Integer(rhs)) => Some(lhs.cmp(rhs)),
+            (ExpressionKind::Float(lhs), ExpressionKind::Float(rhs)) => lhs.partial_cmp(rhs),
+            (ExpressionKind::Duration(lhs), ExpressionKind::Duration(rhs)) => Some(lhs.cmp(rhs)),
+            (ExpressionKind::LiteralString(lhs), ExpressionKind::LiteralString(rhs)) => {
+                Some(lhs.cmp(rhs))
+            }
+            _ => None,
+        }
+    }
+
+    fn compare(op: BinaryOp, ordering: std::cmp::Ordering) -> bool {
+        match op {
+            BinaryOp::LessThan => ordering.is_lt(),
+            BinaryOp::GreaterThan => ordering.is_gt(),
+            BinaryOp::LessEqual => ordering.is_le(),
+            BinaryOp::GreaterEqual => ordering.is_ge(),
+            _ => unreachable!("{:?} is not a comparison operator", op),
+        }
+    }
+
+    fn to_display_string(value: &Expression) -> String {
+        match &value.kind {
+            ExpressionKind::LiteralString(str) => str.clone(),
+            ExpressionKind::Bool(bool) => bool.to_string(),
+            ExpressionKind::Atom(atom) => format!("#{}", atom),
+            ExpressionKind::Integer(int) => int.to_string(),
+            ExpressionKind::Float(float) => float.to_string(),
+            ExpressionKind::Duration(duration) => format!("{}ms", duration.as_millis()),
+            ExpressionKind::Unit => "()".to_string(),
+            ExpressionKind::Tuple(values) if values.len() == 1 => {
+                format!("({},)", Self::to_nested_display_string(&values[0]))
+            }
+            ExpressionKind::Tuple(values) => format!("({})", Self::to_display_list(values)),
+            ExpressionKind::List(values) => format!("[{}]", Self::to_display_list(values)),
+            ExpressionKind::Variant { name, args, .. } if args.is_empty() => name.0.clone(),
+            ExpressionKind::Variant { name, args, .. } => {
+                format!("{}({})", name.0, Self::to_display_list(args))
+            }
+            ExpressionKind::Struct { name, fields, .. } => {
+                let fields: Vec<String> = fields
+                    .iter()
+                    .map(|(field, value)| {
+                        format!("{}: {}", field.0, Self::to_nested_display_string(value))
+                    })
+                    .collect();
+                format!("{} {{ {} }}", name.0, fields.join(", "))
+            }
+            kind => format!("{:?}", kind),
+        }
+    }
+
+    fn to_display_list(values: &[Expression]) -> String {
+        values
+            .iter()
+            .map(Self::to_nested_display_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Strings inside tuples and lists keep their quotes, so `["a, b"]` and
+    /// `["a", "b"]` don't look the same.
+    fn to_nested_display_string(value: &Expression) -> String {
+        match &value.kind {
+            ExpressionKind::LiteralString(str) => format!("{:?}", str),
+            _ => Self::to_display_string(value),
+        }
+    }
+
+    /// Evaluates the callee, unless it names a builtin or an enum variant,
+    /// and then calls it with the arguments.
+    fn eval_call(
+        &mut self,
+        callee: Expression,
+        args: Vec<Expression>,
+        span: Span,
+    ) -> Result<Expression, InterpreterError> {
+        match callee.kind {
+            ExpressionKind::Variable(id) if id == Id("print".to_string()) => self.print(args, span),
+            ExpressionKind::Variable(id) if self.local_variant(&id).is_some() => {
+                self.construct_variant(self.module.clone(), id, args, span)
+            }
+            ExpressionKind::Remote(id) if is_builtin(&id, &["std", "io"], "print") => {
+                self.print(args, span)
+            }
+            ExpressionKind::Remote(id) if self.variant(&id.path, &id.name).is_some() => {
+                self.construct_variant(id.path, id.name, args, span)
+            }
+            _ => {
+                let callee = self.eval(callee)?;
+                let args = self.eval_all(args)?;
+                self.call(callee, args, span)
+            }
+        }
+    }
+
+    fn print(&mut self, args: Vec<Expression>, span: Span) -> Result<Expression, InterpreterError> {
+        let args_exprs: Vec<ExpressionKind> = self
+            .eval_all(args)?
+            .into_iter()
+            .map(|arg| arg.kind)
+            .collect();
+        print!("{:?}", args_exprs);
+        Ok(Expression::new(
+            ExpressionKind::LiteralString("ok".to_string()),
+            span,
+        ))
+    }
+
+    /// Builds the variant `name` of the module at `module`, which must
+    /// declare it.
+    fn construct_variant(
+        &mut self,
+        module: Vec<Id>,
+        name: Id,
         args: Vec<Expression>,
+        span: Span,
     ) -> Result<Expression, InterpreterError> {
-        let mut args_exprs = vec![];
-        for arg in args {
-            args_exprs.push(self.eval(arg)?);
+        let expected = self.types[&module].variants[&name];
+        if args.len() != expected {
+            return Err(InterpreterError::WrongNumberOfArguments {
+                name,
+                expected,
+                found: args.len(),
+                span: span.into(),
+            });
         }
-        self.env.push_scope();
+        let args = self.eval_all(args)?;
+        Ok(Expression::new(
+            ExpressionKind::Variant { name, args, module },
+            span,
+        ))
+    }
+
+    fn lookup_remote(&self, id: &RemoteId, span: Span) -> Result<Expression, InterpreterError> {
+        let Some(module) = self.modules.get(&id.path) else {
+            return Err(InterpreterError::UnknownModule {
+                id: id.clone(),
+                span: span.into(),
+            });
+        };
+        module
+            .lookup(id.name.clone())
+            .map_err(InterpreterError::environment(span))
+    }
+
+    /// Calls a function value with arguments that are already evaluated. The
+    /// body runs among the values of the module the function was written in,
+    /// with the local bindings it captured back in scope.
+    fn call(
+        &mut self,
+        callee: Expression,
+        args: Vec<Expression>,
+        span: Span,
+    ) -> Result<Expression, InterpreterError> {
+        let ExpressionKind::Closure {
+            clauses,
+            captured,
+            module,
+        } = callee.kind
+        else {
+            return Err(InterpreterError::CannotCallNonFunctionValue {
+                expr: callee,
+                span: span.into(),
+            });
+        };
 
-        let result = self.bind_matching_clause(clauses, args_exprs)?;
+        let mut env = self.modules[&module].clone();
+        env.push_scope();
+        for (id, value) in captured {
+            env.bind(id, value);
+        }
+        let caller_env = std::mem::replace(&mut self.env, env);
+        let caller_module = std::mem::replace(&mut self.module, module);
+        let result = self.bind_matching_clause(clauses, args, span);
+        self.env = caller_env;
+        self.module = caller_module;
+        result
+    }
 
+    /// Runs `f` in a new scope holding `bindings`. The scope is popped again
+    /// however `f` returns, errors included.
+    fn in_scope<T>(
+        &mut self,
+        bindings: Vec<(Id, Expression)>,
+        span: Span,
+        f: impl FnOnce(&mut Self) -> Result<T, InterpreterError>,
+    ) -> Result<T, InterpreterError> {
+        self.env.push_scope();
+        for (id, value) in bindings {
+            self.env.bind(id, value);
+        }
+        let result = f(self);
         self.env
             .pop_scope()
-            .map_err(InterpreterError::EnvironmentError)?;
+            .map_err(InterpreterError::environment(span))?;
+        result
+    }
 
-        Ok(result)
+    /// Whether a clause or arm with this guard applies, with its pattern
+    /// bindings already in scope. No guard always passes.
+    fn guard_passes(&mut self, guard: Option<Expression>) -> Result<bool, InterpreterError> {
+        let Some(guard) = guard else {
+            return Ok(true);
+        };
+        let span = guard.span;
+        match self.eval(guard)? {
+            Expression {
+                kind: ExpressionKind::Bool(passes),
+                ..
+            } => Ok(passes),
+            value => Err(InterpreterError::GuardNotABool {
+                value,
+                span: span.into(),
+            }),
+        }
     }
 
+    /// Runs the body of the first clause whose patterns match the arguments
+    /// and whose guard passes, in a scope of its own.
     fn bind_matching_clause(
         &mut self,
         clauses: Vec<FunClause>,
         args_expr: Vec<Expression>,
+        span: Span,
     ) -> Result<Expression, InterpreterError> {
         for clause in clauses {
-            if clause.args.len() != args_expr.len() {
+            let Some(bindings) = self.match_patterns(&clause.args, &args_expr) else {
                 continue;
-            }
+            };
 
-            // TODO(@ostera): make sure the entire pattern matches all the arguments
-            // and ONLY THEN bind
-            for (pattern, value) in clause.args.iter().zip(args_expr.iter()) {
-                if let Pattern::Bind(id) = pattern {
-                    self.env.bind(id.clone(), value.clone());
+            let result = self.in_scope(bindings, span, |interpreter| {
+                if !interpreter.guard_passes(clause.guard)? {
+                    return Ok(None);
                 }
+                interpreter.eval(clause.body).map(Some)
+            });
+            if let Some(result) = self.blame(result)? {
+                return Ok(result);
             }
-
-            return self.eval(clause.body);
         }
-        Err(InterpreterError::ClauseMatchError)
+        // the span is the call, so this error belongs to the caller's code
+        Err(InterpreterError::ClauseMatchError { span: span.into() })
     }
 }
 
+/// Whether two values are equal, no matter where each of them was written.
+fn same_value(lhs: &Expression, rhs: &Expression) -> bool {
+    lhs.clone().without_spans() == rhs.clone().without_spans()
+}
+
+/// Whether `id` names the builtin `name` of the builtin `module`.
+fn is_builtin(id: &RemoteId, module: &[&str], name: &str) -> bool {
+    id.name.0 == name
+        && id
+            .path
+            .iter()
+            .map(|id| id.0.as_str())
+            .eq(module.iter().copied())
+}
+
+#[cfg(test)]
 mod tests {
+    use q_core::testing::TempProject;
     use q_parser::Parser;
 
     use super::*;
@@ -135,13 +1072,850 @@ mod tests {
 
         let mut interpreter = Interpreter::new(module);
 
+        let result = interpreter
+            .eval(
+                ExpressionKind::Call {
+                    callee: Box::new(ExpressionKind::Variable(Id("main".to_string())).into()),
+                    args: vec![ExpressionKind::LiteralString("hello world".to_string()).into()],
+                }
+                .into(),
+            )
+            .unwrap()
+            .without_spans();
+
+        assert_eq!(
+            result,
+            ExpressionKind::LiteralString("ok".to_string()).into()
+        );
+    }
+
+    /// Loads and resolves `files`, given as relative paths and contents,
+    /// starting from `main.q`.
+    fn load(files: &[(&str, &str)]) -> ModuleGraph {
+        let project = TempProject::new(files);
+        let mut loader = q_parser::loader::ModuleLoader::default();
+        let mut graph = loader.load(&project.path("main.q")).unwrap();
+        assert!(loader.diagnostics().is_empty());
+        assert!(q_parser::resolver::resolve(&mut graph).is_empty());
+        graph
+    }
+
+    fn eval_main(program: &str, arg: Expression) -> Result<Expression, InterpreterError> {
+        let mut parser = Parser::from_string("test_module", program);
+        let module = parser.parse().unwrap();
+        assert!(parser.diagnostics().is_empty());
+
+        call_main(&mut Interpreter::new(module), arg)
+    }
+
+    /// Calls `main` with `arg`, and returns the result without its spans.
+    fn call_main(
+        interpreter: &mut Interpreter,
+        arg: Expression,
+    ) -> Result<Expression, InterpreterError> {
         interpreter
-            .eval(Expression::Call {
-                id: Id("main".to_string()),
-                args: vec![Expression::LiteralString("hello world".to_string())],
+            .eval(
+                ExpressionKind::Call {
+                    callee: Box::new(ExpressionKind::Variable(Id("main".to_string())).into()),
+                    args: vec![arg],
+                }
+                .into(),
+            )
+            .map(StripSpans::without_spans)
+    }
+
+    #[test]
+    fn binary_operators_test() {
+        let program = r#"
+            main = (name) { "hello " + name == "hello world" && "a" < name }
+        "#;
+
+        assert_eq!(
+            eval_main(
+                program,
+                ExpressionKind::LiteralString("world".to_string()).into()
+            )
+            .unwrap(),
+            ExpressionKind::Bool(true).into()
+        );
+        assert_eq!(
+            eval_main(
+                program,
+                ExpressionKind::LiteralString("joe".to_string()).into()
+            )
+            .unwrap(),
+            ExpressionKind::Bool(false).into()
+        );
+    }
+
+    #[test]
+    fn binary_operators_short_circuit_test() {
+        let program = r#"
+            main = (flag) { !flag || undefined_fn() }
+        "#;
+
+        assert_eq!(
+            eval_main(program, ExpressionKind::Bool(false).into()).unwrap(),
+            ExpressionKind::Bool(true).into()
+        );
+        assert!(matches!(
+            eval_main(program, ExpressionKind::Bool(true).into()),
+            Err(InterpreterError::EnvironmentError { .. })
+        ));
+    }
+
+    #[test]
+    fn binary_operators_reject_invalid_operands_test() {
+        let program = r#"
+            main = (name) { name * name }
+        "#;
+
+        assert!(matches!(
+            eval_main(
+                program,
+                ExpressionKind::LiteralString("joe".to_string()).into()
+            ),
+            Err(InterpreterError::InvalidOperands {
+                op: BinaryOp::Mul,
+                ..
             })
-            .unwrap();
+        ));
+    }
+
+    #[test]
+    fn numeric_operators_test() {
+        let program = r#"
+            square = (x) { x * x }
+            main = (x) { square(x) + 0x10 % 3 - -1 == 18 && 1.5 * 2.0 > 2.9 && 1s > 999ms }
+        "#;
 
-        assert!(false);
+        assert_eq!(
+            eval_main(program, ExpressionKind::Integer(4).into()).unwrap(),
+            ExpressionKind::Bool(true).into()
+        );
+    }
+
+    #[test]
+    fn numeric_operators_errors_test() {
+        let program = r#"
+            main = (x) { 10 / x }
+        "#;
+
+        assert!(matches!(
+            eval_main(program, ExpressionKind::Integer(0).into()),
+            Err(InterpreterError::DivisionByZero { .. })
+        ));
+        assert!(matches!(
+            eval_main(program, ExpressionKind::Float(2.0).into()),
+            Err(InterpreterError::InvalidOperands { .. })
+        ));
+
+        let program = r#"
+            main = (x) { x * 2 }
+        "#;
+
+        assert!(matches!(
+            eval_main(program, ExpressionKind::Integer(i64::MAX).into()),
+            Err(InterpreterError::ArithmeticOverflow { .. })
+        ));
+    }
+
+    #[test]
+    fn runtime_errors_point_at_source_test() {
+        let program = "main = (x) { 1 + 10 / x }";
+
+        match eval_main(program, ExpressionKind::Integer(0).into()) {
+            Err(InterpreterError::DivisionByZero { span }) => {
+                assert_eq!(
+                    &program[span.offset()..span.offset() + span.len()],
+                    "10 / x"
+                )
+            }
+            result => panic!("expected a division by zero, found {:?}", result),
+        }
+
+        match eval_main(program, ExpressionKind::Bool(true).into()) {
+            Err(InterpreterError::InvalidOperands { span, .. }) => {
+                assert_eq!(
+                    &program[span.offset()..span.offset() + span.len()],
+                    "10 / x"
+                )
+            }
+            result => panic!("expected invalid operands, found {:?}", result),
+        }
+    }
+
+    #[test]
+    fn match_test() {
+        let program = r#"
+            main = (x) { match x * 2 { doubled => `{x} doubled is {doubled}`, other => other } }
+        "#;
+
+        assert_eq!(
+            eval_main(program, ExpressionKind::Integer(21).into()).unwrap(),
+            ExpressionKind::LiteralString("21 doubled is 42".to_string()).into()
+        );
+    }
+
+    #[test]
+    fn calling_any_expression_test() {
+        let program = r#"
+            doubler = () { (y) { y * 2 } }
+            main = (x) {
+                pair = ((y) { y + 1 }, 0)
+                inc = match pair { (f, _) => f }
+                ((y) { y - 3 })(inc(doubler()(x)))
+            }
+        "#;
+
+        assert_eq!(
+            eval_main(program, ExpressionKind::Integer(5).into()).unwrap(),
+            ExpressionKind::Integer(8).into()
+        );
+    }
+
+    #[test]
+    fn closures_keep_the_scope_they_were_made_in_test() {
+        let program = r#"
+            make_adder = (n) { (x) { x + n } }
+            apply = (f, n) { f(n) }
+            main = (x) {
+                add_one = make_adder(1)
+                (make_adder(1)(2), apply(add_one, x))
+            }
+        "#;
+
+        assert_eq!(
+            eval_main(program, ExpressionKind::Integer(10).into()).unwrap(),
+            ExpressionKind::Tuple(vec![
+                ExpressionKind::Integer(3).into(),
+                ExpressionKind::Integer(11).into(),
+            ])
+            .into()
+        );
+    }
+
+    #[test]
+    fn functions_do_not_see_the_scope_of_their_caller_test() {
+        let program = r#"
+            peek = () { secret }
+            main = (secret) { peek() }
+        "#;
+
+        assert!(matches!(
+            eval_main(program, ExpressionKind::Integer(1).into()),
+            Err(InterpreterError::EnvironmentError {
+                error: EnvironmentError::UndefinedSymbol { .. },
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn calling_a_non_function_test() {
+        let program = "main = (x) { x(1) }";
+
+        match eval_main(program, ExpressionKind::Integer(1).into()) {
+            Err(InterpreterError::CannotCallNonFunctionValue { expr, .. }) => {
+                assert_eq!(expr, ExpressionKind::Integer(1).into());
+            }
+            result => panic!("expected a call error, found {:?}", result),
+        }
+    }
+
+    #[test]
+    fn match_without_a_matching_arm_test() {
+        let program = "main = (x) { match x {} }";
+
+        match eval_main(program, ExpressionKind::Integer(1).into()) {
+            Err(InterpreterError::MatchError { value, span }) => {
+                assert_eq!(value, ExpressionKind::Integer(1).into());
+                assert_eq!(
+                    &program[span.offset()..span.offset() + span.len()],
+                    "match x {}"
+                )
+            }
+            result => panic!("expected a match error, found {:?}", result),
+        }
+    }
+
+    #[test]
+    fn function_clauses_match_literal_patterns_test() {
+        let program = r#"
+            fact = (0) { 1 }; (n) { n * fact(n - 1) }
+            describe = (#ok, _) { "fine" }; (#error, true) { "retry" }; (status as s, _) { `{s}?` }
+            main = (x) { `{fact(x)} {describe(#ok, 1)} {describe(#error, true)} {describe(#wat, 1)}` }
+        "#;
+
+        assert_eq!(
+            eval_main(program, ExpressionKind::Integer(5).into()).unwrap(),
+            ExpressionKind::LiteralString("120 fine retry #wat?".to_string()).into()
+        );
+    }
+
+    #[test]
+    fn function_declaration_shorthand_test() {
+        let program = r#"
+            fact(0) { 1 }
+            fact(n) { n * fact(n - 1) }
+            main(x) { fact(x) }
+        "#;
+
+        assert_eq!(
+            eval_main(program, ExpressionKind::Integer(5).into()).unwrap(),
+            ExpressionKind::Integer(120).into()
+        );
+    }
+
+    #[test]
+    fn clauses_only_bind_when_every_pattern_matches_test() {
+        let program = r#"
+            f = (x, 0) { x }; (_, _) { x }
+            main = (y) { f(y, 1) }
+        "#;
+
+        assert!(matches!(
+            eval_main(program, ExpressionKind::Integer(1).into()),
+            Err(InterpreterError::EnvironmentError {
+                error: EnvironmentError::UndefinedSymbol { .. },
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn match_literal_patterns_test() {
+        let program = r#"
+            main = (x) { match x { 0 => "zero", -1 => "minus one", 1.5 => "float", n => `other {n}` } }
+        "#;
+
+        for (arg, expected) in [
+            (ExpressionKind::Integer(0), "zero"),
+            (ExpressionKind::Integer(-1), "minus one"),
+            (ExpressionKind::Float(1.5), "float"),
+            (ExpressionKind::Integer(7), "other 7"),
+        ] {
+            assert_eq!(
+                eval_main(program, arg.into()).unwrap(),
+                ExpressionKind::LiteralString(expected.to_string()).into()
+            );
+        }
+    }
+
+    #[test]
+    fn block_test() {
+        let program = r#"
+            main = (a) {
+                x = a
+                x = x + 1
+                y = { x = x * 10; x }
+                `{x} {y}`
+            }
+        "#;
+
+        assert_eq!(
+            eval_main(program, ExpressionKind::Integer(1).into()).unwrap(),
+            ExpressionKind::LiteralString("2 20".to_string()).into()
+        );
+    }
+
+    #[test]
+    fn block_bindings_do_not_outlive_the_block_test() {
+        let program = r#"
+            main = (a) {
+                { inner = a; inner }
+                inner
+            }
+        "#;
+
+        assert!(matches!(
+            eval_main(program, ExpressionKind::Integer(1).into()),
+            Err(InterpreterError::EnvironmentError {
+                error: EnvironmentError::UndefinedSymbol { .. },
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn mutable_assignment_test() {
+        let program = r#"
+            main = (a) {
+                mut y = a
+                { y <- y + 2 }
+                y
+            }
+        "#;
+
+        assert_eq!(
+            eval_main(program, ExpressionKind::Integer(1).into()).unwrap(),
+            ExpressionKind::Integer(3).into()
+        );
+    }
+
+    #[test]
+    fn assigning_to_an_immutable_binding_test() {
+        let program = "main = (a) { y = a; y <- 2; y }";
+
+        match eval_main(program, ExpressionKind::Integer(1).into()) {
+            Err(InterpreterError::EnvironmentError {
+                error: EnvironmentError::AssignToImmutable { id },
+                span,
+            }) => {
+                assert_eq!(id, Id("y".to_string()));
+                assert_eq!(
+                    &program[span.offset()..span.offset() + span.len()],
+                    "y <- 2"
+                );
+            }
+            result => panic!("expected an assignment error, found {:?}", result),
+        }
+    }
+
+    #[test]
+    fn tuple_and_list_values_test() {
+        let program = r#"
+            sum = ([]) { 0 }; ([head | tail]) { head + sum(tail) }
+            swap = ((a, b)) { (b, a) }
+            main = (xs) {
+                pair = swap((sum(xs), "total"))
+                [pair == ("total", 6), () == (), [1, 2] != [2, 1], `{pair} {xs} {(1,)}`]
+            }
+        "#;
+
+        assert_eq!(
+            eval_main(
+                program,
+                ExpressionKind::List(vec![
+                    ExpressionKind::Integer(1).into(),
+                    ExpressionKind::Integer(2).into(),
+                    ExpressionKind::Integer(3).into(),
+                ])
+                .into()
+            )
+            .unwrap(),
+            ExpressionKind::List(vec![
+                ExpressionKind::Bool(true).into(),
+                ExpressionKind::Bool(true).into(),
+                ExpressionKind::Bool(true).into(),
+                ExpressionKind::LiteralString(r#"("total", 6) [1, 2, 3] (1,)"#.to_string()).into(),
+            ])
+            .into()
+        );
+    }
+
+    #[test]
+    fn struct_and_enum_values_test() {
+        let program = r#"
+            struct User { name: String, role: Role }
+            enum Role { Admin, Guest(String) }
+
+            describe = (User { name, role: Admin }) { `{name} is an admin` };
+                       (User { name, role: Guest(from) }) { `{name} is a guest from {from}` }
+
+            main = (name) {
+                admin = User { role: Admin, name }
+                guest = { admin | role: Guest("Norway") }
+                [describe(admin), describe(guest), admin.name, `{guest}`, admin == User { name: name, role: Admin }]
+            }
+        "#;
+
+        assert_eq!(
+            eval_main(
+                program,
+                ExpressionKind::LiteralString("joe".to_string()).into()
+            )
+            .unwrap(),
+            ExpressionKind::List(vec![
+                ExpressionKind::LiteralString("joe is an admin".to_string()).into(),
+                ExpressionKind::LiteralString("joe is a guest from Norway".to_string()).into(),
+                ExpressionKind::LiteralString("joe".to_string()).into(),
+                ExpressionKind::LiteralString(
+                    r#"User { name: "joe", role: Guest("Norway") }"#.to_string()
+                )
+                .into(),
+                ExpressionKind::Bool(true).into(),
+            ])
+            .into()
+        );
+    }
+
+    #[test]
+    fn recursive_enum_test() {
+        let program = r#"
+            enum Nat { Zero, Succ(Nat) }
+            to_int = (Zero) { 0 }; (Succ(n)) { 1 + to_int(n) }
+            main = (x) { to_int(Succ(Succ(Succ(Zero)))) == x }
+        "#;
+
+        assert_eq!(
+            eval_main(program, ExpressionKind::Integer(3).into()).unwrap(),
+            ExpressionKind::Bool(true).into()
+        );
+    }
+
+    #[test]
+    fn struct_and_enum_errors_test() {
+        let declarations = r#"
+            struct User { name: String }
+            enum Nat { Zero, Succ(Nat) }
+        "#;
+        let eval = |main: &str| {
+            eval_main(
+                &format!("{}\n{}", declarations, main),
+                ExpressionKind::Unit.into(),
+            )
+        };
+
+        assert!(matches!(
+            eval("main = (x) { User {} }"),
+            Err(InterpreterError::MissingField { .. })
+        ));
+        assert!(matches!(
+            eval("main = (x) { User { name: 1, age: 2 } }"),
+            Err(InterpreterError::UnknownField { .. })
+        ));
+        assert!(matches!(
+            eval("main = (x) { User { name: 1 }.age }"),
+            Err(InterpreterError::UnknownField { .. })
+        ));
+        assert!(matches!(
+            eval("main = (x) { Admin { name: 1 } }"),
+            Err(InterpreterError::UnknownStruct { .. })
+        ));
+        assert!(matches!(
+            eval("main = (x) { { x | name: 1 } }"),
+            Err(InterpreterError::NotAStruct { .. })
+        ));
+        assert!(matches!(
+            eval("main = (x) { Succ(Zero, Zero) }"),
+            Err(InterpreterError::WrongNumberOfArguments {
+                expected: 1,
+                found: 2,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn atoms_test() {
+        let program = r##"
+            status = (code) { #ok }
+            main = (x) { status(x) == #ok && status(x) != #error && `{status(x)}` == "#ok" }
+        "##;
+
+        assert_eq!(
+            eval_main(program, ExpressionKind::Integer(200).into()).unwrap(),
+            ExpressionKind::Bool(true).into()
+        );
+    }
+
+    #[test]
+    fn interpolated_string_test() {
+        let program = r#"
+            greet = (name) { `Hello {name}!` }
+            main = (Arg) { `{greet(Arg)} {"bye"}` }
+        "#;
+        let mut parser = Parser::from_string("test_module", program);
+        let module = parser.parse().unwrap();
+
+        let mut interpreter = Interpreter::new(module);
+
+        let result = interpreter
+            .eval(
+                ExpressionKind::Call {
+                    callee: Box::new(ExpressionKind::Variable(Id("main".to_string())).into()),
+                    args: vec![ExpressionKind::LiteralString("world".to_string()).into()],
+                }
+                .into(),
+            )
+            .unwrap()
+            .without_spans();
+
+        assert_eq!(
+            result,
+            ExpressionKind::LiteralString("Hello world! bye".to_string()).into()
+        );
+    }
+
+    #[test]
+    fn remote_calls_run_in_their_own_module_test() {
+        let graph = load(&[
+            ("main.q", "mod math\nmain = (x) { math:two(x) }"),
+            ("math.q", "mod nat\ntwo = (x) { (nat:one(), x) }"),
+            (
+                "math/nat.q",
+                "enum Nat { Zero, Succ(Nat) }\none = () { succ(Zero) }\nsucc = (n) { Succ(n) }",
+            ),
+        ]);
+
+        let mut interpreter = Interpreter::from_graph(&graph);
+        let result = call_main(&mut interpreter, ExpressionKind::Integer(2).into()).unwrap();
+
+        let nat: Vec<Id> = ["main", "math", "nat"]
+            .iter()
+            .map(|name| Id(name.to_string()))
+            .collect();
+        let zero = ExpressionKind::Variant {
+            name: Id("Zero".to_string()),
+            args: vec![],
+            module: nat.clone(),
+        };
+        assert_eq!(
+            result,
+            ExpressionKind::Tuple(vec![
+                ExpressionKind::Variant {
+                    name: Id("Succ".to_string()),
+                    args: vec![zero.into()],
+                    module: nat,
+                }
+                .into(),
+                ExpressionKind::Integer(2).into(),
+            ])
+            .into()
+        );
+    }
+
+    #[test]
+    fn patterns_match_types_of_other_modules_test() {
+        let graph = load(&[
+            (
+                "main.q",
+                r#"
+                mod flags
+                mod geo
+                enum Local { On }
+                struct Point { x: Int }
+                main = (arg) {
+                    (
+                        match flags:off() { flags:On => "on", _ => "off" },
+                        match flags:on() { On => "local", flags:On => "remote" },
+                        match flags:level(2) { flags:Level(n) => n, _ => 0 },
+                        match geo:origin() { Point { x } => x, geo:Point { x } => x + 1 },
+                    )
+                }
+                "#,
+            ),
+            (
+                "flags.q",
+                "enum Flag { On, Off, Level(Int) }
+                 on = () { On }
+                 off = () { Off }
+                 level = (n) { Level(n) }",
+            ),
+            (
+                "geo.q",
+                "struct Point { x: Int }
+origin = () { Point { x: 0 } }",
+            ),
+        ]);
+
+        let mut interpreter = Interpreter::from_graph(&graph);
+        assert_eq!(
+            call_main(&mut interpreter, ExpressionKind::Unit.into()).unwrap(),
+            ExpressionKind::Tuple(vec![
+                ExpressionKind::LiteralString("off".to_string()).into(),
+                ExpressionKind::LiteralString("remote".to_string()).into(),
+                ExpressionKind::Integer(2).into(),
+                ExpressionKind::Integer(1).into(),
+            ])
+            .into()
+        );
+    }
+
+    #[test]
+    fn errors_know_the_module_that_raised_them_test() {
+        let path =
+            |names: &[&str]| -> Vec<Id> { names.iter().map(|name| Id(name.to_string())).collect() };
+        let math = ("math.q", "invert = (x) { 1 / x }");
+
+        let graph = load(&[
+            ("main.q", "mod math\nmain = (arg) { math:invert(0) }"),
+            math,
+        ]);
+        match Interpreter::from_graph(&graph).main() {
+            Err(RuntimeError {
+                module,
+                error: InterpreterError::DivisionByZero { .. },
+            }) => assert_eq!(module, path(&["main", "math"])),
+            result => panic!("expected a division by zero, found {:?}", result),
+        }
+
+        let graph = load(&[("main.q", "mod math\nmain = (arg) { math:invert() }"), math]);
+        match Interpreter::from_graph(&graph).main() {
+            Err(RuntimeError {
+                module,
+                error: InterpreterError::ClauseMatchError { .. },
+            }) => assert_eq!(module, path(&["main"])),
+            result => panic!("expected a clause match error, found {:?}", result),
+        }
+    }
+
+    #[test]
+    fn failing_guards_fall_through_to_the_next_clause_test() {
+        let program = r#"
+            sign(n) when n > 0 { "positive" }
+            sign(n) when n < 0 { "negative" }
+            sign(_) { "zero" }
+            size = (n) {
+                match n {
+                    x when x > 100 => "big",
+                    _ => "small",
+                }
+            }
+            main = (n) { `{sign(n)} {size(n)}` }
+        "#;
+        for (arg, expected) in [
+            (5, "positive small"),
+            (-3, "negative small"),
+            (0, "zero small"),
+            (500, "positive big"),
+        ] {
+            assert_eq!(
+                eval_main(program, ExpressionKind::Integer(arg).into()).unwrap(),
+                ExpressionKind::LiteralString(expected.to_string()).into()
+            );
+        }
+    }
+
+    #[test]
+    fn guards_must_be_booleans_test() {
+        let program = "main = (n) { match n { x when x + 1 => x } }";
+        assert!(matches!(
+            eval_main(program, ExpressionKind::Integer(1).into()),
+            Err(InterpreterError::GuardNotABool { .. })
+        ));
+    }
+
+    #[test]
+    fn errors_do_not_leave_scopes_behind_test() {
+        let program = "main = () { 1 }";
+        let mut parser = Parser::from_string("test_module", program);
+        let mut interpreter = Interpreter::new(parser.parse().unwrap());
+
+        // match 1 { x when 1 => x }
+        let x = || Id("x".to_string());
+        let failing = ExpressionKind::Match {
+            expr: Box::new(ExpressionKind::Integer(1).into()),
+            arms: vec![MatchArm {
+                pattern: Pattern::new(PatternKind::Bind(x()), Span::default()),
+                guard: Some(ExpressionKind::Integer(1).into()),
+                body: ExpressionKind::Variable(x()).into(),
+                span: Span::default(),
+            }],
+        };
+        assert!(matches!(
+            interpreter.eval(failing.into()),
+            Err(InterpreterError::GuardNotABool { .. })
+        ));
+
+        assert!(matches!(
+            interpreter.eval(ExpressionKind::Variable(x()).into()),
+            Err(InterpreterError::EnvironmentError {
+                error: EnvironmentError::UndefinedSymbol { .. },
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn method_calls_are_ordinary_calls_on_the_receiver_test() {
+        let program = r#"
+            double = (n) { n * 2 }
+            add = (a, b) { a + b }
+            main = (x) { x.double().add(1) }
+        "#;
+        assert_eq!(
+            eval_main(program, ExpressionKind::Integer(3).into()).unwrap(),
+            ExpressionKind::Integer(7).into()
+        );
+    }
+
+    #[test]
+    fn method_calls_look_in_the_module_of_the_receiver_type_test() {
+        let graph = load(&[
+            (
+                "main.q",
+                "mod user\nname = (x) { 0 }\nmain = (x) { user:new(x).name() }",
+            ),
+            (
+                "user.q",
+                "struct User { name: String }\nnew = (name) { User { name } }\nname = (u) { u.name }",
+            ),
+        ]);
+
+        let mut interpreter = Interpreter::from_graph(&graph);
+        let joe: Expression = ExpressionKind::LiteralString("joe".to_string()).into();
+        assert_eq!(call_main(&mut interpreter, joe.clone()).unwrap(), joe);
+    }
+
+    #[test]
+    fn function_values_run_in_the_module_they_come_from_test() {
+        let graph = load(&[
+            ("main.q", "mod math\nmain = (x) { f = math:two\n f(x) }"),
+            ("math.q", "one = () { 1 }\ntwo = (x) { (one(), x) }"),
+        ]);
+
+        let mut interpreter = Interpreter::from_graph(&graph);
+        assert_eq!(
+            call_main(&mut interpreter, ExpressionKind::Integer(2).into()).unwrap(),
+            ExpressionKind::Tuple(vec![
+                ExpressionKind::Integer(1).into(),
+                ExpressionKind::Integer(2).into(),
+            ])
+            .into()
+        );
+    }
+
+    #[test]
+    fn types_belong_to_the_module_that_declares_them_test() {
+        let graph = load(&[
+            (
+                "main.q",
+                "mod other\n\
+                 struct Point { x: Int }\n\
+                 label = (p) { \"main\" }\n\
+                 main = (x) { (Point { x }.label(), other:point(x).label()) }\n\
+                 hidden = () { On }\n\
+                 shadowed = (On) { On }",
+            ),
+            (
+                "other.q",
+                "enum Flag { On }\n\
+                 struct Point { y: Int }\n\
+                 point = (y) { Point { y } }\n\
+                 label = (p) { \"other\" }",
+            ),
+        ]);
+        let string =
+            |str: &str| -> Expression { ExpressionKind::LiteralString(str.to_string()).into() };
+
+        let mut interpreter = Interpreter::from_graph(&graph);
+        assert_eq!(
+            call_main(&mut interpreter, ExpressionKind::Integer(1).into()).unwrap(),
+            ExpressionKind::Tuple(vec![string("main"), string("other")]).into()
+        );
+
+        let call = |name: &str, args: Vec<Expression>| -> Expression {
+            ExpressionKind::Call {
+                callee: Box::new(ExpressionKind::Variable(Id(name.to_string())).into()),
+                args,
+            }
+            .into()
+        };
+        assert!(matches!(
+            interpreter.eval(call("hidden", vec![])),
+            Err(InterpreterError::EnvironmentError {
+                error: EnvironmentError::UndefinedSymbol { .. },
+                ..
+            })
+        ));
+        assert_eq!(
+            interpreter
+                .eval(call("shadowed", vec![string("local")]))
+                .unwrap()
+                .without_spans(),
+            string("local")
+        );
     }
 }
@@ -4,12 +4,40 @@ mod interpreter;
 fn main() -> miette::Result<()> {
     for file in std::env::args().skip(1) {
         let path = std::path::PathBuf::from(&file);
-        let mut parser = q_parser::Parser::from_file(&path)?;
-        let module = parser.parse()?;
-        let () = parser.diagnostics().unwrap();
-        let interpreter = interpreter::Interpreter::new(module);
+        let mut loader = q_parser::loader::ModuleLoader::default();
+        let mut graph = loader.load(&path)?;
+        let diagnostics = loader.take_diagnostics();
+        if !diagnostics.is_empty() {
+            for diagnostic in diagnostics {
+                eprintln!("{:?}", miette::Report::new(diagnostic));
+            }
+            std::process::exit(1);
+        }
+        let diagnostics = q_parser::resolver::resolve(&mut graph);
+        if !diagnostics.is_empty() {
+            for diagnostic in diagnostics {
+                eprintln!("{:?}", miette::Report::new(diagnostic));
+            }
+            std::process::exit(1);
+        }
+        let attributes = q_macros::attributes::AttributeRegistry::default();
+        for (_, loaded) in graph.iter() {
+            for warning in attributes.check(&loaded.module) {
+                let report = miette::Report::new(warning).with_source_code(loaded.source.clone());
+                eprintln!("{:?}", report);
+            }
+        }
+        let interpreter = interpreter::Interpreter::from_graph(&graph);
 
-        interpreter.main().unwrap();
+        if let Err(error) = interpreter.main() {
+            let (_, module) = graph
+                .iter()
+                .find(|(_, loaded)| loaded.path == error.module)
+                .expect("errors are raised by loaded modules");
+            let report = miette::Report::new(error.error).with_source_code(module.source.clone());
+            eprintln!("{:?}", report);
+            std::process::exit(1);
+        }
     }
     Ok(())
 }
@@ -1,9 +1,10 @@
-pub trait Diagnostic: Send + Sync + std::fmt::Debug {
-}
-
+/// Anything the compiler reports back to the user. Every diagnostic is also a
+/// `miette::Diagnostic`, so it can be rendered with a labeled snippet of the
+/// source it points at.
+pub trait Diagnostic: miette::Diagnostic + Send + Sync + std::fmt::Debug {}
 
 #[derive(Debug)]
 pub struct Diagnostics {
     pub errors: Vec<Box<dyn Diagnostic>>,
-    pub warnings: Vec<Box<dyn Diagnostic>>
+    pub warnings: Vec<Box<dyn Diagnostic>>,
 }
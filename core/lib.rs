@@ -1 +1,4 @@
 pub mod diagnostic;
+pub mod suggest;
+#[cfg(feature = "testing")]
+pub mod testing;
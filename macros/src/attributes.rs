@@ -0,0 +1,108 @@
+use miette::{Diagnostic, SourceSpan};
+use q_parser::parsetree::*;
+use std::collections::HashSet;
+use thiserror::Error;
+
+#[derive(Error, Diagnostic, Clone, Debug, PartialEq)]
+#[error("No macro claims the attribute @{name}, so it will be ignored")]
+#[diagnostic(severity(Warning))]
+pub struct UnknownAttribute {
+    pub name: String,
+    #[label("unknown attribute")]
+    pub span: SourceSpan,
+}
+
+impl q_core::diagnostic::Diagnostic for UnknownAttribute {}
+
+/// The attributes that macros have claimed. Any other attribute is reported
+/// with a warning and otherwise left alone.
+#[derive(Default, Debug)]
+pub struct AttributeRegistry {
+    claimed: HashSet<String>,
+}
+
+impl AttributeRegistry {
+    /// Claims every attribute whose path is `name`, like `derive` or
+    /// `std.derive`.
+    pub fn claim(&mut self, name: &str) {
+        self.claimed.insert(name.to_string());
+    }
+
+    pub fn is_claimed(&self, attribute: &Attribute) -> bool {
+        self.claimed.contains(&attribute.name())
+    }
+
+    /// Warns about every attribute in the module that no macro has claimed.
+    pub fn check(&self, module: &Module) -> Vec<UnknownAttribute> {
+        let mut attributes = vec![];
+        for item in &module.items {
+            match item {
+                ModuleItem::ValueDeclaration(vd) => attributes.extend(&vd.attributes),
+                ModuleItem::StructDeclaration(decl) => {
+                    attributes.extend(&decl.attributes);
+                    for field in &decl.fields {
+                        attributes.extend(&field.attributes);
+                    }
+                }
+                ModuleItem::EnumDeclaration(decl) => {
+                    attributes.extend(&decl.attributes);
+                    for variant in &decl.variants {
+                        attributes.extend(&variant.attributes);
+                    }
+                }
+                ModuleItem::ModDeclaration(decl) => attributes.extend(&decl.attributes),
+            }
+        }
+
+        attributes
+            .into_iter()
+            .filter(|attribute| !self.is_claimed(attribute))
+            .map(|attribute| UnknownAttribute {
+                name: attribute.name(),
+                span: attribute.span.into(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use q_parser::Parser;
+
+    #[test]
+    fn unclaimed_attributes_are_reported() {
+        let mut parser = Parser::from_string(
+            "test_module",
+            r#"
+@derive(Debug)
+struct User {
+    @std.rename("user_name")
+    name: String
+}
+
+@cli
+main = (args) { args }
+"#,
+        );
+        let module = parser.parse().unwrap();
+        assert_eq!(parser.diagnostics(), vec![]);
+
+        let mut registry = AttributeRegistry::default();
+        registry.claim("derive");
+
+        assert_eq!(
+            registry.check(&module),
+            vec![
+                UnknownAttribute {
+                    name: "std.rename".to_string(),
+                    span: (34, 24).into()
+                },
+                UnknownAttribute {
+                    name: "cli".to_string(),
+                    span: (79, 4).into()
+                },
+            ]
+        );
+    }
+}
@@ -1,56 +1,192 @@
+use crate::token::{has_unknown_duration_unit, is_integer_literal, Token};
 use miette::*;
 use thiserror::Error;
-use crate::token::Token;
 
-#[derive(Error, Clone, Debug)]
+#[derive(Error, Diagnostic, Clone, Debug, PartialEq)]
+pub enum LexError {
+    #[error("We don't know what to make of {slice:?}")]
+    UnexpectedCharacters {
+        slice: String,
+        #[label("not a valid token")]
+        span: SourceSpan,
+    },
+
+    #[error("The number {slice} is too large")]
+    #[diagnostic(help(
+        "number literals must fit in an unsigned 64-bit integer, and durations in that many milliseconds"
+    ))]
+    NumberTooLarge {
+        slice: String,
+        #[label("this literal overflows")]
+        span: SourceSpan,
+    },
+
+    #[error("{slice:?} isn't a duration we know")]
+    #[diagnostic(help("durations end in `ms`, `s` or `m`, like `500ms`, `30s` or `5m`"))]
+    UnknownDurationUnit {
+        slice: String,
+        #[label("unknown unit")]
+        span: SourceSpan,
+    },
+
+    #[error("This block comment is never closed")]
+    #[diagnostic(help("block comments nest, so every `/*` needs its own `*/`"))]
+    UnterminatedBlockComment {
+        #[label("the comment starts here")]
+        span: SourceSpan,
+    },
+}
+
+impl LexError {
+    pub fn new(slice: &str, span: SourceSpan) -> Self {
+        if slice.starts_with("/*") {
+            Self::UnterminatedBlockComment {
+                span: (span.offset(), 2).into(),
+            }
+        } else if is_integer_literal(slice) {
+            // well-formed integer literals only fail to lex when they overflow
+            Self::NumberTooLarge {
+                slice: slice.to_string(),
+                span,
+            }
+        } else if has_unknown_duration_unit(slice) {
+            Self::UnknownDurationUnit {
+                slice: slice.to_string(),
+                span,
+            }
+        } else {
+            Self::UnexpectedCharacters {
+                slice: slice.to_string(),
+                span,
+            }
+        }
+    }
+}
+
+#[derive(Error, Diagnostic, Clone, Debug, PartialEq)]
 pub enum ParseError {
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    LexError(LexError),
+
     #[error("We were expecting a {expected:?}, but instead found: {found:?}")]
-    UnexpectedSymbolFound { expected: Token, found: Token },
+    UnexpectedSymbolFound {
+        expected: Token,
+        found: Token,
+        #[label("unexpected {found:?}")]
+        span: SourceSpan,
+    },
+
+    #[error("`{keyword}` is a reserved keyword and can't be used as a name")]
+    ReservedKeyword {
+        keyword: &'static str,
+        #[label("reserved keyword")]
+        span: SourceSpan,
+    },
 
     #[error("We were expecting an expression, but instead found: {found:?}")]
-    ExpectedExpression { found: Token },
+    ExpectedExpression {
+        found: Token,
+        #[label("expected an expression here")]
+        span: SourceSpan,
+    },
 
     #[error("We were expecting a pattern, but instead found: {found:?}")]
-    ExpectedPattern { found: Token },
+    ExpectedPattern {
+        found: Token,
+        #[label("expected a pattern here")]
+        span: SourceSpan,
+    },
 
     #[error("When parsing module, we found a declaration without a value.")]
     MissingValueInValueDeclaration {
+        #[label("this declaration needs a value")]
         span: SourceSpan,
-        src: String,
     },
 
-    #[error("We reached the end of the file")]
-    EOF,
-}
+    #[error("This string interpolation is missing its closing brace: {found:?}")]
+    UnterminatedInterpolation {
+        found: String,
+        #[label("this brace is never closed")]
+        span: SourceSpan,
+    },
 
-impl PartialEq for ParseError {
-    fn eq(&self, other: &Self) -> bool {
-        match (self, other) {
-            (
-                Self::UnexpectedSymbolFound {
-                    expected: l_expected,
-                    found: l_found,
-                },
-                Self::UnexpectedSymbolFound {
-                    expected: r_expected,
-                    found: r_found,
-                },
-            ) => l_expected == r_expected && l_found == r_found,
-            (
-                Self::ExpectedPattern { found: l_found },
-                Self::ExpectedPattern { found: r_found },
-            ) => l_found == r_found,
-            (
-                Self::ExpectedExpression { found: l_found },
-                Self::ExpectedExpression { found: r_found },
-            ) => l_found == r_found,
-            (
-                Self::MissingValueInValueDeclaration { span: l_span, .. },
-                Self::MissingValueInValueDeclaration { span: r_span, .. },
-            ) => l_span == r_span,
-            _ => core::mem::discriminant(self) == core::mem::discriminant(other),
-        }
-    }
+    #[error("We found an unknown escape sequence in a string: \\{found}")]
+    UnknownEscapeSequence {
+        found: char,
+        #[label("unknown escape sequence")]
+        span: SourceSpan,
+    },
+
+    #[error("The integer {slice} is out of range")]
+    #[diagnostic(help("integers must fit in a signed 64-bit integer"))]
+    IntegerTooLarge {
+        slice: String,
+        #[label("this literal overflows")]
+        span: SourceSpan,
+    },
+
+    #[error("The operator {found:?} can't be chained, use parenthesis to group it")]
+    NonAssociativeOperator {
+        found: Token,
+        #[label("can't be chained")]
+        span: SourceSpan,
+    },
+
+    #[error("We found {found:?} right after a statement on the same line")]
+    #[diagnostic(help("separate statements with a `;` or a line break"))]
+    MissingStatementSeparator {
+        found: Token,
+        #[label("expected a `;` or a line break before this")]
+        span: SourceSpan,
+    },
+
+    #[error("This receive has no arms, so no message could ever match it")]
+    EmptyReceive {
+        #[label("expected at least one `pattern => expression` arm")]
+        span: SourceSpan,
+    },
+
+    #[error("The clauses of {name} are split up by other declarations")]
+    #[diagnostic(help("keep every clause of a function next to each other"))]
+    SplitFunctionClauses {
+        name: String,
+        #[label("this clause is away from the others")]
+        span: SourceSpan,
+        #[label("the function starts here")]
+        first: SourceSpan,
+    },
+
+    #[error("{name} is declared more than once")]
+    #[diagnostic(help(
+        "only functions declared like `{name}(args) {{ body }}` can have several clauses"
+    ))]
+    DuplicateDeclaration {
+        name: String,
+        #[label("declared again here")]
+        span: SourceSpan,
+        #[label("first declared here")]
+        first: SourceSpan,
+    },
+
+    #[error("The arguments of this attribute are never closed")]
+    #[diagnostic(help("close the arguments with a `)` before the item starts"))]
+    UnclosedAttributeArguments {
+        #[label("unclosed attribute arguments")]
+        span: SourceSpan,
+    },
+
+    #[error("We found a doc comment that is not followed by a declaration")]
+    DanglingDocComment {
+        #[label("this doc comment documents nothing")]
+        span: SourceSpan,
+    },
+
+    #[error("We reached the end of the file")]
+    EOF {
+        #[label("the file ends here")]
+        span: SourceSpan,
+    },
 }
 
 impl q_core::diagnostic::Diagnostic for ParseError {}
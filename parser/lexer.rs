@@ -1,43 +1,212 @@
-use crate::error::ParseError;
+use crate::error::{LexError, ParseError};
 use crate::token::Token;
 use logos::Logos;
 use miette::SourceSpan;
 
+/// A position in the token stream that the [Lexer] can be rewound to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Checkpoint {
+    position: usize,
+    brace_depth: usize,
+}
+
 pub struct Lexer<'source> {
     lexer: logos::Lexer<'source, Token>,
-    peeked: Option<Token>,
+    /// Every token pulled out of logos so far, so we can look ahead as far as
+    /// we need to and rewind to any earlier checkpoint.
+    tokens: Vec<(Token, SourceSpan)>,
+    /// The index in `tokens` of the next token `next` will return.
+    position: usize,
+    /// How many braces the tokens before `position` leave open.
+    brace_depth: usize,
+    /// Where `source` starts within its file, added to every span.
+    offset: usize,
+    errors: Vec<LexError>,
 }
 
 impl<'source> Lexer<'source> {
     pub fn from_source(source: &'source str) -> Self {
+        Self::from_source_at(source, 0)
+    }
+
+    /// Lexes a `source` that starts `offset` bytes into its file, like the
+    /// code embedded in a string interpolation, so that spans still point
+    /// at the right place in the file.
+    pub fn from_source_at(source: &'source str, offset: usize) -> Self {
         let lexer = Token::lexer(source);
         Self {
             lexer,
-            peeked: None,
+            tokens: vec![],
+            position: 0,
+            brace_depth: 0,
+            offset,
+            errors: vec![],
         }
     }
 
+    /// The span of the last token returned by `next`, or an empty span at
+    /// the start of the source if there isn't one yet.
     pub fn span(&self) -> SourceSpan {
-        let range = self.lexer.span();
-        (range.start, range.end - range.start).into()
+        match self.position.checked_sub(1) {
+            Some(last) => self.tokens[last].1,
+            None => (self.offset, 0).into(),
+        }
+    }
+
+    /// The span of the token `peek` would return.
+    pub fn peek_span(&mut self) -> Option<SourceSpan> {
+        self.fill(0).then(|| self.tokens[self.position].1)
+    }
+
+    /// Where the next token starts, or where the source ends if there are no
+    /// tokens left.
+    pub fn next_offset(&mut self) -> usize {
+        self.peek_span().unwrap_or_else(|| self.eof_span()).offset()
+    }
+
+    /// The span from `start` up to the end of the last token returned by
+    /// `next`.
+    pub fn span_from(&self, start: usize) -> SourceSpan {
+        let last = self.span();
+        let end = (last.offset() + last.len()).max(start);
+        (start, end - start).into()
+    }
+
+    /// Whether a line break separates the last token returned by `next` from
+    /// the one `peek` would return.
+    pub fn next_starts_line(&mut self) -> bool {
+        self.nth_starts_line(0)
+    }
+
+    /// Whether a line break separates the token `peek_nth(n)` would return
+    /// from the one before it.
+    pub fn nth_starts_line(&mut self, n: usize) -> bool {
+        if !self.fill(n) {
+            return false;
+        }
+        let next = self.tokens[self.position + n].1;
+        let last = match n {
+            0 => self.span(),
+            _ => self.tokens[self.position + n - 1].1,
+        };
+        let between = (last.offset() + last.len()).saturating_sub(self.offset)
+            ..next.offset().saturating_sub(self.offset);
+        self.lexer
+            .source()
+            .get(between)
+            .map_or(false, |trivia| trivia.contains('\n'))
     }
 
+    /// The source text under `span`.
+    pub fn slice(&self, span: SourceSpan) -> &'source str {
+        let start = span.offset() - self.offset;
+        &self.lexer.source()[start..start + span.len()]
+    }
+
+    /// An empty span right at the end of the source.
+    pub fn eof_span(&self) -> SourceSpan {
+        (self.offset + self.lexer.source().len(), 0).into()
+    }
+
+    #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> Result<Token, ParseError> {
-        if let Some(peeked) = self.peeked.take() {
-            Ok(peeked)
-        } else {
-            match self.lexer.next() {
-                None => Err(ParseError::EOF),
-                Some(token) => Ok(token),
-            }
+        if !self.fill(0) {
+            return Err(ParseError::EOF {
+                span: self.eof_span(),
+            });
+        }
+        let (token, _) = self.tokens[self.position].clone();
+        self.position += 1;
+        match token {
+            Token::BraceLeft => self.brace_depth += 1,
+            Token::BraceRight => self.brace_depth = self.brace_depth.saturating_sub(1),
+            _ => (),
         }
+        Ok(token)
     }
 
     pub fn peek(&mut self) -> Option<Token> {
-        if self.peeked.is_none() {
-            self.peeked = self.lexer.next();
+        self.peek_nth(0)
+    }
+
+    /// Looks `n` tokens past the next one without consuming anything, so
+    /// `peek_nth(0)` is the same as `peek()`.
+    pub fn peek_nth(&mut self, n: usize) -> Option<Token> {
+        self.fill(n)
+            .then(|| self.tokens[self.position + n].0.clone())
+    }
+
+    /// How many braces are open at the current position.
+    pub fn brace_depth(&self) -> usize {
+        self.brace_depth
+    }
+
+    /// Remembers the current position, so that after trying out a production
+    /// we can `rewind` as if none of its tokens had been consumed.
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            position: self.position,
+            brace_depth: self.brace_depth,
+        }
+    }
+
+    pub fn rewind(&mut self, checkpoint: Checkpoint) {
+        self.position = checkpoint.position;
+        self.brace_depth = checkpoint.brace_depth;
+    }
+
+    /// Runs `f` and rewinds to where we were before it, so `f` can consume
+    /// as many tokens as it needs to decide something without the caller
+    /// losing any of them.
+    pub fn lookahead<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        let checkpoint = self.checkpoint();
+        let result = f(self);
+        self.rewind(checkpoint);
+        result
+    }
+
+    /// The errors found so far while lexing, in the order they were found.
+    pub fn take_errors(&mut self) -> Vec<LexError> {
+        std::mem::take(&mut self.errors)
+    }
+
+    /// Makes sure the token `n` places past the current position has been
+    /// lexed. Returns false if the source ends before that.
+    fn fill(&mut self, n: usize) -> bool {
+        while self.tokens.len() <= self.position + n {
+            match self.next_token() {
+                Some(token) => {
+                    let span = self.logos_span();
+                    self.tokens.push((token, span));
+                }
+                None => return false,
+            }
         }
-        self.peeked.clone()
+        true
+    }
+
+    /// Pulls the next token out of logos, skipping over trivia. Doc comments
+    /// are kept since the parser attaches them to declarations.
+    ///
+    /// Anything that doesn't lex is recorded as a [LexError] and skipped, so
+    /// the parser only ever sees valid tokens and the rest of the file still
+    /// gets checked.
+    fn next_token(&mut self) -> Option<Token> {
+        loop {
+            match self.lexer.next() {
+                Some(token) if token.is_trivia() => continue,
+                Some(Token::Error) => {
+                    let span = self.logos_span();
+                    self.errors.push(LexError::new(self.lexer.slice(), span));
+                }
+                token => return token,
+            }
+        }
+    }
+
+    fn logos_span(&self) -> SourceSpan {
+        let range = self.lexer.span();
+        (self.offset + range.start, range.end - range.start).into()
     }
 
     pub fn expect(&mut self, expected: Token) -> Result<(), ParseError> {
@@ -45,11 +214,70 @@ impl<'source> Lexer<'source> {
         if found == expected {
             Ok(())
         } else {
-            Err(ParseError::UnexpectedSymbolFound { expected, found })
+            Err(ParseError::UnexpectedSymbolFound {
+                expected,
+                found,
+                span: self.span(),
+            })
         }
     }
 }
 
+/// A token along with the exact slice of source it was lexed from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LosslessToken<'source> {
+    pub token: Token,
+    pub slice: &'source str,
+    pub span: SourceSpan,
+}
+
+/// Lexes `source` keeping every single token, trivia and errors included, for
+/// tools like formatters and syntax highlighters that need to work on the
+/// source as it was written. Concatenating the slices of the returned tokens
+/// gives back the original source byte for byte (see [to_source]).
+pub fn lex_lossless(source: &str) -> Vec<LosslessToken> {
+    let mut tokens = vec![];
+    let mut lexer = Token::lexer(source);
+    let mut last_end = 0;
+
+    while let Some(token) = lexer.next() {
+        let range = lexer.span();
+
+        // logos should cover every byte, but if it ever skips some we keep
+        // them as an error rather than silently losing them.
+        if range.start > last_end {
+            tokens.push(LosslessToken {
+                token: Token::Error,
+                slice: &source[last_end..range.start],
+                span: (last_end, range.start - last_end).into(),
+            });
+        }
+
+        tokens.push(LosslessToken {
+            token,
+            slice: lexer.slice(),
+            span: (range.start, range.end - range.start).into(),
+        });
+        last_end = range.end;
+    }
+
+    if last_end < source.len() {
+        tokens.push(LosslessToken {
+            token: Token::Error,
+            slice: &source[last_end..],
+            span: (last_end, source.len() - last_end).into(),
+        });
+    }
+
+    tokens
+}
+
+/// Prints a lossless token stream back out as source code.
+pub fn to_source(tokens: &[LosslessToken]) -> String {
+    tokens.iter().map(|token| token.slice).collect()
+}
+
+#[cfg(test)]
 mod tests {
     use super::*;
 
@@ -66,4 +294,263 @@ mod tests {
         assert!(lex.next().is_err());
         assert_eq!(lex.peek(), None);
     }
+
+    #[test]
+    fn peek_nth_looks_ahead_without_consuming() {
+        let mut lex = Lexer::from_source("a ( b ) { }");
+
+        assert_eq!(lex.peek_nth(4), Some(Token::BraceLeft));
+        assert_eq!(lex.peek_nth(1), Some(Token::ParensLeft));
+        assert_eq!(lex.peek_nth(6), None);
+        assert_eq!(lex.next().unwrap(), Token::Id("a".to_string()));
+        assert_eq!(lex.peek_nth(0), Some(Token::ParensLeft));
+        assert_eq!(lex.peek_nth(4), Some(Token::BraceRight));
+        assert_eq!(lex.peek_nth(5), None);
+    }
+
+    #[test]
+    fn next_starts_line_looks_at_the_trivia_before_the_next_token() {
+        let mut lex = Lexer::from_source("f (x)\n// comment\n(y)");
+
+        lex.next().unwrap();
+        assert!(!lex.next_starts_line());
+        for _ in 0..3 {
+            lex.next().unwrap();
+        }
+        assert!(lex.next_starts_line());
+    }
+
+    #[test]
+    fn nth_starts_line_looks_at_the_trivia_before_that_token() {
+        let mut lex = Lexer::from_source("a b\n c");
+
+        assert!(!lex.nth_starts_line(1));
+        assert!(lex.nth_starts_line(2));
+        assert!(!lex.nth_starts_line(3));
+        lex.next().unwrap();
+        lex.next().unwrap();
+        assert!(lex.next_starts_line());
+    }
+
+    #[test]
+    fn rewind_restores_tokens_and_spans() {
+        let mut lex = Lexer::from_source("a ( b ) { }");
+
+        assert_eq!(lex.next().unwrap(), Token::Id("a".to_string()));
+        let checkpoint = lex.checkpoint();
+        assert_eq!(lex.span(), (0, 1).into());
+        assert_eq!(lex.peek_span(), Some((2, 1).into()));
+
+        assert_eq!(lex.next().unwrap(), Token::ParensLeft);
+        assert_eq!(lex.next().unwrap(), Token::Id("b".to_string()));
+        assert_eq!(lex.next().unwrap(), Token::ParensRight);
+        assert_eq!(lex.span(), (6, 1).into());
+
+        lex.rewind(checkpoint);
+        assert_eq!(lex.span(), (0, 1).into());
+        assert_eq!(lex.peek_span(), Some((2, 1).into()));
+        assert_eq!(lex.peek(), Some(Token::ParensLeft));
+        assert_eq!(lex.next().unwrap(), Token::ParensLeft);
+        assert_eq!(lex.span(), (2, 1).into());
+    }
+
+    #[test]
+    fn brace_depth_counts_open_braces() {
+        let mut lex = Lexer::from_source("{ { } { } } }");
+
+        assert_eq!(lex.brace_depth(), 0);
+        lex.next().unwrap();
+        lex.next().unwrap();
+        assert_eq!(lex.brace_depth(), 2);
+        lex.next().unwrap();
+        lex.next().unwrap();
+        assert_eq!(lex.brace_depth(), 2);
+        lex.next().unwrap();
+        lex.next().unwrap();
+        assert_eq!(lex.brace_depth(), 0);
+        lex.next().unwrap();
+        assert_eq!(lex.brace_depth(), 0);
+    }
+
+    #[test]
+    fn rewind_restores_the_brace_depth() {
+        let mut lex = Lexer::from_source("{ { } }");
+
+        lex.next().unwrap();
+        let checkpoint = lex.checkpoint();
+        lex.next().unwrap();
+        assert_eq!(lex.brace_depth(), 2);
+        lex.rewind(checkpoint);
+        assert_eq!(lex.brace_depth(), 1);
+        lex.next().unwrap();
+        lex.next().unwrap();
+        assert_eq!(lex.brace_depth(), 1);
+    }
+
+    #[test]
+    fn rewind_does_not_report_lex_errors_twice() {
+        let mut lex = Lexer::from_source("a $ b");
+
+        let checkpoint = lex.checkpoint();
+        assert_eq!(lex.next().unwrap(), Token::Id("a".to_string()));
+        assert_eq!(lex.next().unwrap(), Token::Id("b".to_string()));
+        lex.rewind(checkpoint);
+        assert_eq!(lex.next().unwrap(), Token::Id("a".to_string()));
+        assert_eq!(lex.next().unwrap(), Token::Id("b".to_string()));
+        assert_eq!(lex.take_errors().len(), 1);
+    }
+
+    #[test]
+    fn lookahead_consumes_nothing() {
+        let mut lex = Lexer::from_source("{ a } b");
+
+        let found = lex.lookahead(|lex| {
+            lex.next().unwrap();
+            lex.next().unwrap()
+        });
+        assert_eq!(found, Token::Id("a".to_string()));
+        assert_eq!(lex.brace_depth(), 0);
+        assert_eq!(lex.next().unwrap(), Token::BraceLeft);
+    }
+
+    #[test]
+    fn spans_are_offset_into_the_file() {
+        let mut lex = Lexer::from_source_at("a $", 10);
+
+        assert_eq!(lex.span(), (10, 0).into());
+        assert_eq!(lex.span_from(10), (10, 0).into());
+
+        assert_eq!(lex.next().unwrap(), Token::Id("a".to_string()));
+        assert_eq!(lex.span(), (10, 1).into());
+        assert_eq!(
+            lex.next(),
+            Err(ParseError::EOF {
+                span: (13, 0).into()
+            })
+        );
+        assert_eq!(
+            lex.take_errors(),
+            vec![LexError::UnexpectedCharacters {
+                slice: "$".to_string(),
+                span: (12, 1).into()
+            }]
+        );
+    }
+
+    #[test]
+    fn lex_errors_are_recorded_and_skipped() {
+        let mut lex = Lexer::from_source("1 $ 2 /* 3");
+
+        assert_eq!(lex.next().unwrap(), Token::Number(1));
+        assert_eq!(lex.next().unwrap(), Token::Number(2));
+        assert!(lex.next().is_err());
+        assert_eq!(
+            lex.take_errors(),
+            vec![
+                LexError::UnexpectedCharacters {
+                    slice: "$".to_string(),
+                    span: (2, 1).into()
+                },
+                LexError::UnterminatedBlockComment {
+                    span: (6, 2).into()
+                },
+            ]
+        );
+        assert_eq!(lex.take_errors(), vec![]);
+    }
+
+    #[test]
+    fn unknown_duration_units_are_one_error() {
+        let mut lex = Lexer::from_source("5min + 1");
+
+        assert_eq!(lex.next().unwrap(), Token::Plus);
+        assert_eq!(lex.next().unwrap(), Token::Number(1));
+        assert_eq!(
+            lex.take_errors(),
+            vec![LexError::UnknownDurationUnit {
+                slice: "5min".to_string(),
+                span: (0, 4).into()
+            }]
+        );
+    }
+
+    #[test]
+    fn lossless_lexing_keeps_trivia() {
+        let tokens = lex_lossless("x = 1 // one\n");
+
+        assert_eq!(
+            tokens,
+            vec![
+                LosslessToken {
+                    token: Token::Id("x".to_string()),
+                    slice: "x",
+                    span: (0, 1).into()
+                },
+                LosslessToken {
+                    token: Token::Whitespace,
+                    slice: " ",
+                    span: (1, 1).into()
+                },
+                LosslessToken {
+                    token: Token::Equal,
+                    slice: "=",
+                    span: (2, 1).into()
+                },
+                LosslessToken {
+                    token: Token::Whitespace,
+                    slice: " ",
+                    span: (3, 1).into()
+                },
+                LosslessToken {
+                    token: Token::Number(1),
+                    slice: "1",
+                    span: (4, 1).into()
+                },
+                LosslessToken {
+                    token: Token::Whitespace,
+                    slice: " ",
+                    span: (5, 1).into()
+                },
+                LosslessToken {
+                    token: Token::LineComment,
+                    slice: "// one",
+                    span: (6, 6).into()
+                },
+                LosslessToken {
+                    token: Token::Whitespace,
+                    slice: "\n",
+                    span: (12, 1).into()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn lossless_lexing_round_trips() {
+        let sources = [
+            "",
+            "   \r\n\t",
+            include_str!("../DESIGN.md"),
+            include_str!("../CAPTAINS_LOG.md"),
+            "/// docs\nhello = (name) { `Hello {name}!` } /* a /* nested */ comment */",
+            "weird $ ~ \"unterminated string\n 0xFFFFFFFFFFFFFFFFFFFF /* unterminated",
+            "ünïcödé = 名前 + #atom ≠ 1_000ms",
+        ];
+
+        for source in sources {
+            let tokens = lex_lossless(source);
+            assert_eq!(to_source(&tokens), source);
+        }
+    }
+
+    #[test]
+    fn comments_are_skipped() {
+        let mut lex = Lexer::from_source("1 // one\n /* two */ 2 /// three");
+
+        assert_eq!(lex.next().unwrap(), Token::Number(1));
+        assert_eq!(lex.peek(), Some(Token::Number(2)));
+        assert_eq!(lex.next().unwrap(), Token::Number(2));
+        assert_eq!(lex.next().unwrap(), Token::DocComment("three".to_string()));
+        assert_eq!(lex.peek(), None);
+    }
 }
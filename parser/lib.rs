@@ -1,7 +1,9 @@
 pub mod error;
+pub mod lexer;
+pub mod loader;
 mod parser;
 pub mod parsetree;
+pub mod resolver;
 pub mod token;
-pub mod lexer;
 
 pub use parser::*;
@@ -0,0 +1,351 @@
+use crate::error::ParseError;
+use crate::parsetree::*;
+use crate::Parser;
+use miette::{Diagnostic, NamedSource, SourceSpan};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use thiserror::Error;
+
+#[derive(Error, Diagnostic, Debug)]
+pub enum LoaderError {
+    #[error("We could not read {}", .path.display())]
+    CannotReadFile {
+        path: PathBuf,
+        #[source]
+        error: std::io::Error,
+    },
+
+    #[error("We could not parse {}", .path.display())]
+    Parse {
+        path: PathBuf,
+        #[source_code]
+        src: Arc<NamedSource>,
+        #[related]
+        errors: Vec<ParseError>,
+    },
+
+    #[error("The module {name} has no file")]
+    #[diagnostic(help("create {}", .expected.display()))]
+    MissingModule {
+        name: String,
+        expected: PathBuf,
+        #[source_code]
+        src: Arc<NamedSource>,
+        #[label("declared here")]
+        span: SourceSpan,
+    },
+
+    #[error("The module {name} is declared twice")]
+    DuplicateModule {
+        name: String,
+        #[source_code]
+        src: Arc<NamedSource>,
+        #[label("declared again here")]
+        span: SourceSpan,
+    },
+
+    #[error("The module {name} contains itself")]
+    #[diagnostic(help("{} is already being loaded", .path.display()))]
+    ModuleCycle {
+        name: String,
+        path: PathBuf,
+        #[source_code]
+        src: Arc<NamedSource>,
+        #[label("this declaration leads back to a module that declares it")]
+        span: SourceSpan,
+    },
+}
+
+impl q_core::diagnostic::Diagnostic for LoaderError {}
+
+/// Where a module lives in a [`ModuleGraph`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ModuleId(pub usize);
+
+#[derive(Debug)]
+pub struct LoadedModule {
+    /// The names leading from the entry module to this one, starting with the
+    /// entry module itself, like `main`, `math`, `nat`.
+    pub path: Vec<Id>,
+    pub file: PathBuf,
+    pub source: Arc<NamedSource>,
+    pub module: Module,
+    pub submodules: HashMap<Id, ModuleId>,
+}
+
+/// Every module reachable from an entry file through `mod` declarations.
+#[derive(Debug, Default)]
+pub struct ModuleGraph {
+    modules: Vec<LoadedModule>,
+}
+
+impl ModuleGraph {
+    pub fn entry(&self) -> ModuleId {
+        ModuleId(0)
+    }
+
+    pub fn get(&self, id: ModuleId) -> &LoadedModule {
+        &self.modules[id.0]
+    }
+
+    pub fn get_mut(&mut self, id: ModuleId) -> &mut LoadedModule {
+        &mut self.modules[id.0]
+    }
+
+    /// The submodule `name` declared by the module `id`.
+    pub fn submodule(&self, id: ModuleId, name: &Id) -> Option<ModuleId> {
+        self.get(id).submodules.get(name).copied()
+    }
+
+    /// All modules, each one before its submodules.
+    pub fn iter(&self) -> impl Iterator<Item = (ModuleId, &LoadedModule)> {
+        self.modules
+            .iter()
+            .enumerate()
+            .map(|(i, module)| (ModuleId(i), module))
+    }
+
+    pub fn len(&self) -> usize {
+        self.modules.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.modules.is_empty()
+    }
+}
+
+/// Loads a program from its entry file. Files are modules and `mod nat`
+/// declares a submodule: from the entry file `main.q` it is the sibling
+/// `nat.q`, and from any other module `math.q` it is `math/nat.q`.
+#[derive(Debug, Default)]
+pub struct ModuleLoader {
+    diagnostics: Vec<LoaderError>,
+}
+
+impl ModuleLoader {
+    pub fn diagnostics(&self) -> &[LoaderError] {
+        &self.diagnostics
+    }
+
+    pub fn take_diagnostics(&mut self) -> Vec<LoaderError> {
+        std::mem::take(&mut self.diagnostics)
+    }
+
+    /// Loads the entry file and every module it reaches. Only an unreadable
+    /// entry file stops the loader; anything else ends up in the diagnostics
+    /// and the module in question is left out of the graph.
+    pub fn load(&mut self, entry: &Path) -> Result<ModuleGraph, LoaderError> {
+        let source =
+            std::fs::read_to_string(entry).map_err(|error| LoaderError::CannotReadFile {
+                path: entry.to_path_buf(),
+                error,
+            })?;
+        let folder = entry.parent().unwrap_or(Path::new("")).to_path_buf();
+
+        let mut graph = ModuleGraph::default();
+        let mut loading = vec![];
+        self.load_module(&mut graph, &mut loading, vec![], entry, source, folder);
+        Ok(graph)
+    }
+
+    fn load_module(
+        &mut self,
+        graph: &mut ModuleGraph,
+        loading: &mut Vec<PathBuf>,
+        mut path: Vec<Id>,
+        file: &Path,
+        source: String,
+        folder: PathBuf,
+    ) -> ModuleId {
+        let mut parser = Parser::from_source(file, source);
+        let src = parser.named_source();
+        let module = match parser.parse() {
+            Ok(module) => module,
+            Err(error) => {
+                self.diagnostics.push(LoaderError::Parse {
+                    path: file.to_path_buf(),
+                    src: src.clone(),
+                    errors: vec![error],
+                });
+                Module {
+                    name: Id(file.file_stem().unwrap().to_string_lossy().to_string()),
+                    items: vec![],
+                }
+            }
+        };
+        let errors = parser.diagnostics();
+        if !errors.is_empty() {
+            self.diagnostics.push(LoaderError::Parse {
+                path: file.to_path_buf(),
+                src: src.clone(),
+                errors,
+            });
+        }
+
+        path.push(module.name.clone());
+        let declarations: Vec<ModDeclaration> = module
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                ModuleItem::ModDeclaration(decl) => Some(decl.clone()),
+                _ => None,
+            })
+            .collect();
+
+        let id = ModuleId(graph.modules.len());
+        graph.modules.push(LoadedModule {
+            path: path.clone(),
+            file: file.to_path_buf(),
+            source: src.clone(),
+            module,
+            submodules: HashMap::new(),
+        });
+        loading.push(canonical(file));
+
+        let mut declared = HashSet::new();
+        for decl in declarations {
+            let name = decl.name.0.clone();
+            if !declared.insert(decl.name.clone()) {
+                self.diagnostics.push(LoaderError::DuplicateModule {
+                    name,
+                    src: src.clone(),
+                    span: decl.span.into(),
+                });
+                continue;
+            }
+
+            let file = folder.join(format!("{}.q", name));
+            let source = match std::fs::read_to_string(&file) {
+                Ok(source) => source,
+                Err(_) => {
+                    self.diagnostics.push(LoaderError::MissingModule {
+                        name,
+                        expected: file,
+                        src: src.clone(),
+                        span: decl.span.into(),
+                    });
+                    continue;
+                }
+            };
+
+            if loading.contains(&canonical(&file)) {
+                self.diagnostics.push(LoaderError::ModuleCycle {
+                    name,
+                    path: file,
+                    src: src.clone(),
+                    span: decl.span.into(),
+                });
+                continue;
+            }
+
+            let subfolder = folder.join(&name);
+            let submodule =
+                self.load_module(graph, loading, path.clone(), &file, source, subfolder);
+            graph.modules[id.0].submodules.insert(decl.name, submodule);
+        }
+
+        loading.pop();
+        id
+    }
+}
+
+/// The path with symlinks and `..` resolved, so that the same file is always
+/// recognised as such.
+fn canonical(file: &Path) -> PathBuf {
+    file.canonicalize().unwrap_or_else(|_| file.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use q_core::testing::TempProject;
+
+    fn names(path: &[Id]) -> Vec<&str> {
+        path.iter().map(|id| id.0.as_str()).collect()
+    }
+
+    #[test]
+    fn load_follows_mod_declarations() {
+        let project = TempProject::new(&[
+            ("main.q", "mod math\nmain = () { 1 }"),
+            ("math.q", "mod nat\none = () { 1 }"),
+            ("math/nat.q", "enum Nat { Zero, Succ(Nat) }"),
+        ]);
+
+        let mut loader = ModuleLoader::default();
+        let graph = loader.load(&project.path("main.q")).unwrap();
+
+        assert!(loader.diagnostics().is_empty());
+        assert_eq!(graph.len(), 3);
+        let main = graph.entry();
+        let math = graph.submodule(main, &Id("math".to_string())).unwrap();
+        let nat = graph.submodule(math, &Id("nat".to_string())).unwrap();
+        assert_eq!(names(&graph.get(main).path), vec!["main"]);
+        assert_eq!(names(&graph.get(nat).path), vec!["main", "math", "nat"]);
+        assert_eq!(graph.get(nat).file, project.path("math/nat.q"));
+        assert_eq!(graph.get(nat).module.items[0].name().0, "Nat");
+    }
+
+    #[test]
+    fn load_reports_missing_modules() {
+        let project = TempProject::new(&[("main.q", "mod math\nmain = () { 1 }")]);
+
+        let mut loader = ModuleLoader::default();
+        let graph = loader.load(&project.path("main.q")).unwrap();
+
+        assert_eq!(graph.len(), 1);
+        match loader.diagnostics() {
+            [LoaderError::MissingModule {
+                name,
+                expected,
+                span,
+                ..
+            }] => {
+                assert_eq!(name, "math");
+                assert_eq!(expected, &project.path("math.q"));
+                assert_eq!(span, &SourceSpan::from((0, 8)));
+            }
+            diagnostics => panic!("expected a missing module, got {:?}", diagnostics),
+        }
+    }
+
+    #[test]
+    fn load_reports_cycles() {
+        let project = TempProject::new(&[("main.q", "mod main\nmain = () { 1 }")]);
+
+        let mut loader = ModuleLoader::default();
+        let graph = loader.load(&project.path("main.q")).unwrap();
+
+        assert_eq!(graph.len(), 1);
+        assert!(matches!(
+            loader.diagnostics(),
+            [LoaderError::ModuleCycle { name, .. }] if name == "main"
+        ));
+    }
+
+    #[test]
+    fn load_reports_duplicates_and_parse_errors() {
+        let project = TempProject::new(&[("main.q", "mod math\nmod math"), ("math.q", "one = )")]);
+
+        let mut loader = ModuleLoader::default();
+        let graph = loader.load(&project.path("main.q")).unwrap();
+
+        assert_eq!(graph.len(), 2);
+        assert!(matches!(
+            loader.diagnostics(),
+            [LoaderError::Parse { errors, .. }, LoaderError::DuplicateModule { .. }]
+                if errors.len() == 1
+        ));
+    }
+
+    #[test]
+    fn load_fails_without_an_entry_file() {
+        let project = TempProject::new(&[]);
+        let mut loader = ModuleLoader::default();
+        assert!(matches!(
+            loader.load(&project.path("main.q")),
+            Err(LoaderError::CannotReadFile { .. })
+        ));
+    }
+}
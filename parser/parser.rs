@@ -1,29 +1,118 @@
-use q_core::diagnostic::Diagnostics;
 use crate::error::*;
 use crate::lexer::Lexer;
 use crate::parsetree::*;
 use crate::token::*;
-use miette::NamedSource;
+use logos::Logos;
+use miette::{NamedSource, SourceSpan};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Associativity {
+    Left,
+    None,
+}
+
+/// The binary operators, from loosest to tightest binding:
+///
+/// | Precedence | Operators            | Associativity |
+/// |------------|----------------------|---------------|
+/// | 1          | `\|\|`               | left          |
+/// | 2          | `&&`                 | left          |
+/// | 3          | `==` `!=`            | none          |
+/// | 4          | `<` `>` `<=` `>=`    | none          |
+/// | 5          | `+` `-`              | left          |
+/// | 6          | `*` `/` `%`          | left          |
+///
+/// Unary `-` and `!` bind tighter than any binary operator, and function calls
+/// bind tighter than those. A negative number is a literal of its own though,
+/// so `-1.abs()` calls `abs` on `-1` while `-x.abs()` negates `x.abs()`.
+/// Non-associative operators can't be chained, so
+/// `a == b == c` is an error rather than a surprise.
+///
+/// A `-` that starts a new line is a negation starting the next expression
+/// rather than a subtraction continuing the last one, just like a `(` on a
+/// new line doesn't call what came before it.
+fn binary_operator(token: &Token) -> Option<(BinaryOp, u8, Associativity)> {
+    let op = match token {
+        Token::OrOr => (BinaryOp::Or, 1, Associativity::Left),
+        Token::AndAnd => (BinaryOp::And, 2, Associativity::Left),
+        Token::EqualEqual => (BinaryOp::Equal, 3, Associativity::None),
+        Token::NotEqual => (BinaryOp::NotEqual, 3, Associativity::None),
+        Token::LessThan => (BinaryOp::LessThan, 4, Associativity::None),
+        Token::GreaterThan => (BinaryOp::GreaterThan, 4, Associativity::None),
+        Token::LessEqual => (BinaryOp::LessEqual, 4, Associativity::None),
+        Token::GreaterEqual => (BinaryOp::GreaterEqual, 4, Associativity::None),
+        Token::Plus => (BinaryOp::Add, 5, Associativity::Left),
+        Token::Minus => (BinaryOp::Sub, 5, Associativity::Left),
+        Token::Star => (BinaryOp::Mul, 6, Associativity::Left),
+        Token::Slash => (BinaryOp::Div, 6, Associativity::Left),
+        Token::Percent => (BinaryOp::Rem, 6, Associativity::Left),
+        _ => return None,
+    };
+    Some(op)
+}
+
+/// The value of the integer literal at `span`, or an error if it doesn't fit
+/// in an `i64`. The lexer already rejects literals that don't fit in a `u64`.
+fn integer(lexer: &Lexer, value: Option<i64>, span: SourceSpan) -> Result<i64, ParseError> {
+    value.ok_or_else(|| ParseError::IntegerTooLarge {
+        slice: lexer.slice(span).to_string(),
+        span,
+    })
+}
+
+/// Where the `{expr}` segment of an interpolated string that `source` starts
+/// in ends, as the offset of its closing `}`. The segment is lexed like any
+/// other code, so braces inside string literals and comments don't count.
+fn interpolation_end(source: &str) -> Option<usize> {
+    let mut lexer = Token::lexer(source);
+    let mut depth = 0;
+    while let Some(token) = lexer.next() {
+        match token {
+            Token::BraceLeft => depth += 1,
+            Token::BraceRight if depth == 0 => return Some(lexer.span().start),
+            Token::BraceRight => depth -= 1,
+            _ => (),
+        }
+    }
+    None
+}
 
 pub struct Parser {
     filename: PathBuf,
     source: String,
     module_name: String,
     diagnostics: Vec<ParseError>,
+    /// Problems found halfway through an item that don't stop us from
+    /// parsing the rest of it. They join `diagnostics` once the item is done.
+    reported: RefCell<Vec<ParseError>>,
+    /// Whether a `{` can belong to the expression before it, as in a struct
+    /// literal `Name {` or a function `(args) {`. It can't right before the
+    /// `{` that opens the arms of a `match`.
+    brace_literals: Cell<bool>,
 }
 
 impl Parser {
     pub fn from_file(filename: &Path) -> Result<Self, ParseError> {
-        let source = std::fs::read_to_string(&filename).unwrap();
-        let module_name = filename.file_name().unwrap().to_str().unwrap().to_string();
-        let parser = Parser {
+        let source = std::fs::read_to_string(filename).unwrap();
+        Ok(Self::from_source(filename, source))
+    }
+
+    /// A parser for `source`, which was read from `filename`. The module is
+    /// named after the file, without its extension.
+    pub fn from_source(filename: &Path, source: String) -> Self {
+        let module_name = filename.file_stem().unwrap().to_string_lossy().to_string();
+        Self {
             filename: filename.to_path_buf(),
             module_name,
             source,
             diagnostics: vec![],
-        };
-        Ok(parser)
+            reported: RefCell::new(vec![]),
+            brace_literals: Cell::new(true),
+        }
     }
 
     pub fn from_string(module_name: &str, source: &str) -> Self {
@@ -32,6 +121,8 @@ impl Parser {
             module_name: module_name.to_string(),
             source: source.to_string(),
             diagnostics: vec![],
+            reported: RefCell::new(vec![]),
+            brace_literals: Cell::new(true),
         }
     }
 
@@ -43,16 +134,33 @@ impl Parser {
         let mut lexer = Lexer::from_source(&self.source);
 
         let mut items = vec![];
+        let mut declared = HashMap::new();
 
         while lexer.peek().is_some() {
-            match self.parse_module_item(&mut lexer) {
-                Ok(item) => items.push(item),
+            let start = lexer.checkpoint();
+            let item = self.parse_module_item(&mut lexer);
+            self.diagnostics
+                .extend(lexer.take_errors().into_iter().map(ParseError::LexError));
+            self.diagnostics.extend(self.reported.take());
+            match item {
+                Ok((item, shorthand)) => Self::push_item(
+                    &mut items,
+                    &mut declared,
+                    item,
+                    shorthand,
+                    &mut self.diagnostics,
+                ),
                 Err(error) => {
-                    // TODO(@ostera): skip until the next valid token
-                    self.diagnostics.push(error)
+                    self.diagnostics.push(error);
+                    if lexer.checkpoint() == start {
+                        let _ = lexer.next();
+                    }
+                    self.synchronize(&mut lexer);
                 }
             }
         }
+        self.diagnostics
+            .extend(lexer.take_errors().into_iter().map(ParseError::LexError));
 
         Ok(Module {
             name: Id(self.module_name.clone()),
@@ -60,92 +168,397 @@ impl Parser {
         })
     }
 
-    fn named_source(&self) -> NamedSource {
-        NamedSource::new(self.filename.to_str().unwrap(), self.source.clone())
-    }
+    /// Adds the item to the module. A function declared with the shorthand
+    /// right after another one of the same name, like `next(n) { .. }` after
+    /// `next(Zero) { .. }`, adds its clauses to that function instead.
+    ///
+    /// `declared` holds the index in `items` of every value declared so far,
+    /// and whether it was declared with the shorthand.
+    fn push_item(
+        items: &mut Vec<ModuleItem>,
+        declared: &mut HashMap<Id, (usize, bool)>,
+        item: ModuleItem,
+        shorthand: bool,
+        diagnostics: &mut Vec<ParseError>,
+    ) {
+        let ModuleItem::ValueDeclaration(vd) = item else {
+            items.push(item);
+            return;
+        };
+        let Some(&(index, previous_shorthand)) = declared.get(&vd.name) else {
+            declared.insert(vd.name.clone(), (items.len(), shorthand));
+            items.push(ModuleItem::ValueDeclaration(vd));
+            return;
+        };
 
-    fn parse_module_item(&self, lexer: &mut Lexer) -> Result<ModuleItem, ParseError> {
-        let vd = self.parse_value_declaration(lexer)?;
-        Ok(ModuleItem::ValueDeclaration(vd))
+        let last = items.len() - 1;
+        let ModuleItem::ValueDeclaration(previous) = &mut items[index] else {
+            unreachable!("only value declarations are recorded");
+        };
+        if !(shorthand && previous_shorthand) {
+            diagnostics.push(ParseError::DuplicateDeclaration {
+                name: vd.name.0,
+                span: vd.span.into(),
+                first: previous.span.into(),
+            });
+            return;
+        }
+        if index != last {
+            diagnostics.push(ParseError::SplitFunctionClauses {
+                name: vd.name.0.clone(),
+                span: vd.span.into(),
+                first: previous.span.into(),
+            });
+        }
+        if let (ExpressionKind::Function(previous_clauses), ExpressionKind::Function(clauses)) =
+            (&mut previous.value.kind, vd.value.kind)
+        {
+            previous_clauses.extend(clauses);
+        }
+        previous.value.span = previous.value.span.to(vd.value.span);
+        previous.span = previous.span.to(vd.span);
+        previous.attributes.extend(vd.attributes);
+        if previous.doc.is_none() {
+            previous.doc = vd.doc;
+        }
     }
 
-    fn parse_value_declaration(&self, lexer: &mut Lexer) -> Result<ValueDeclaration, ParseError> {
-        let name = self.parse_id(lexer)?;
-        lexer.expect(Token::Equal)?;
-        let value = self.parse_expression(lexer).map_err(|_| {
-            let span = lexer.span();
-            let src = self.named_source();
-            ParseError::MissingValueInValueDeclaration { span, src }
-        })?;
+    fn report(&self, error: ParseError) {
+        self.reported.borrow_mut().push(error);
+    }
 
-        Ok(ValueDeclaration { name, value })
+    /// Panic-mode recovery: after an error, skip tokens until we are back at
+    /// the top level of the module and looking at something that plausibly
+    /// starts a new item. Any braces left open by the broken item get closed
+    /// along the way, so a mistake reports exactly one diagnostic.
+    fn synchronize(&self, lexer: &mut Lexer) {
+        while let Some(token) = lexer.peek() {
+            if lexer.brace_depth() == 0 && self.starts_module_item(lexer, &token) {
+                return;
+            }
+            let _ = lexer.next();
+        }
     }
 
-    fn parse_id(&self, lexer: &mut Lexer) -> Result<Id, ParseError> {
-        match lexer.next()? {
-            Token::Id(id) => Ok(Id(id)),
-            token => Err(ParseError::UnexpectedSymbolFound {
-                expected: Token::Id("some_id".to_string()),
-                found: token,
-            }),
+    fn starts_module_item(&self, lexer: &mut Lexer, token: &Token) -> bool {
+        match token {
+            Token::DocComment(_) | Token::At | Token::Struct | Token::Enum | Token::Mod => true,
+            Token::Id(_) => matches!(lexer.peek_nth(1), Some(Token::Equal | Token::ParensLeft)),
+            _ => false,
         }
     }
 
-    fn parse_expression(&self, lexer: &mut Lexer) -> Result<Expression, ParseError> {
+    pub fn named_source(&self) -> Arc<NamedSource> {
+        Arc::new(NamedSource::new(
+            self.filename.to_str().unwrap(),
+            self.source.clone(),
+        ))
+    }
+
+    /// Parses a module item, and whether it's a function declared with the
+    /// `name(args) { body }` shorthand.
+    fn parse_module_item(&self, lexer: &mut Lexer) -> Result<(ModuleItem, bool), ParseError> {
+        let start = lexer.next_offset();
+        let doc = self.parse_doc_comments(lexer)?;
+        let attributes = self.parse_attributes(lexer)?;
         match lexer.peek() {
-            Some(Token::Id(_)) => {
-                let id = self.parse_id(lexer)?;
+            Some(Token::Struct) => {
+                let decl = self.parse_struct_declaration(lexer, start, doc, attributes)?;
+                Ok((ModuleItem::StructDeclaration(decl), false))
+            }
+            Some(Token::Enum) => {
+                let decl = self.parse_enum_declaration(lexer, start, doc, attributes)?;
+                Ok((ModuleItem::EnumDeclaration(decl), false))
+            }
+            Some(Token::Mod) => {
+                lexer.next()?;
+                let name = self.parse_id(lexer)?;
+                let decl = ModDeclaration {
+                    doc,
+                    attributes,
+                    name,
+                    span: lexer.span_from(start).into(),
+                };
+                Ok((ModuleItem::ModDeclaration(decl), false))
+            }
+            _ => {
+                let shorthand = lexer.peek_nth(1) == Some(Token::ParensLeft);
+                let vd = self.parse_value_declaration(lexer, start, doc, attributes)?;
+                Ok((ModuleItem::ValueDeclaration(vd), shorthand))
+            }
+        }
+    }
 
-                match lexer.peek() {
-                    Some(Token::ParensLeft) => self.parse_function_call(lexer, id),
-                    _ => Ok(Expression::Variable(id)),
+    fn parse_doc_comments(&self, lexer: &mut Lexer) -> Result<Option<String>, ParseError> {
+        let start = lexer.next_offset();
+        let mut lines = vec![];
+        while let Some(Token::DocComment(line)) = lexer.peek() {
+            lexer.next()?;
+            lines.push(line);
+        }
+
+        if lines.is_empty() {
+            return Ok(None);
+        }
+
+        if lexer.peek().is_none() {
+            return Err(ParseError::DanglingDocComment {
+                span: lexer.span_from(start),
+            });
+        }
+
+        Ok(Some(lines.join("\n")))
+    }
+
+    fn parse_attributes(&self, lexer: &mut Lexer) -> Result<Vec<Attribute>, ParseError> {
+        let mut attributes = vec![];
+        while let Some(Token::At) = lexer.peek() {
+            attributes.push(self.parse_attribute(lexer)?);
+        }
+        Ok(attributes)
+    }
+
+    /// Parses `@path.to.name` followed by optional arguments in parens. The
+    /// arguments are only split into token trees, since it's up to whichever
+    /// macro claims the attribute to make sense of them.
+    ///
+    /// Arguments that are still open when the next item starts are reported
+    /// and skipped, so the item they belong to still gets parsed.
+    fn parse_attribute(&self, lexer: &mut Lexer) -> Result<Attribute, ParseError> {
+        let start = lexer.next_offset();
+        lexer.expect(Token::At)?;
+
+        let mut path = vec![self.parse_id(lexer)?];
+        while let Some(Token::Dot) = lexer.peek() {
+            lexer.next()?;
+            path.push(self.parse_id(lexer)?);
+        }
+
+        let args = match lexer.peek() {
+            Some(Token::ParensLeft) if lexer.lookahead(|lexer| self.skip_attribute_args(lexer)) => {
+                match Self::parse_token_tree(lexer)? {
+                    TokenTree::Group { trees, .. } => trees,
+                    TokenTree::Token { .. } => unreachable!("`(` always starts a group"),
                 }
             }
-            Some(Token::LiteralString(str)) => {
+            Some(Token::ParensLeft) => {
+                self.report(ParseError::UnclosedAttributeArguments {
+                    span: (start, 1).into(),
+                });
                 lexer.next()?;
-                Ok(Expression::LiteralString(str))
+                while !self.ends_attribute_args(lexer) {
+                    lexer.next()?;
+                }
+                vec![]
+            }
+            _ => vec![],
+        };
+
+        Ok(Attribute {
+            path,
+            args,
+            span: lexer.span_from(start).into(),
+        })
+    }
+
+    /// Consumes the arguments of an attribute, and returns whether they're
+    /// closed before the item they belong to starts.
+    fn skip_attribute_args(&self, lexer: &mut Lexer) -> bool {
+        let mut depth = 0;
+        while !(depth > 0 && self.ends_attribute_args(lexer)) {
+            match lexer.next() {
+                Ok(Token::ParensLeft | Token::BracketLeft | Token::BraceLeft) => depth += 1,
+                Ok(Token::ParensRight | Token::BracketRight | Token::BraceRight) => depth -= 1,
+                Ok(_) => (),
+                Err(_) => return false,
+            }
+            if depth == 0 {
+                return true;
             }
-            Some(Token::ParensLeft) => self.parse_function(lexer),
-            Some(token) => Err(ParseError::ExpectedExpression { found: token }),
-            None => Err(ParseError::EOF),
         }
+        false
     }
 
-    fn parse_function(&self, lexer: &mut Lexer) -> Result<Expression, ParseError> {
-        let mut clauses = vec![];
+    /// Whether arguments that aren't closed yet have run into the end of the
+    /// file, an item keyword, or a line that starts a new item.
+    fn ends_attribute_args(&self, lexer: &mut Lexer) -> bool {
+        match lexer.peek() {
+            None | Some(Token::Struct | Token::Enum | Token::Mod) => true,
+            Some(token) => lexer.next_starts_line() && self.starts_module_item(lexer, &token),
+        }
+    }
+
+    fn parse_token_tree(lexer: &mut Lexer) -> Result<TokenTree, ParseError> {
+        let start = lexer.next_offset();
+        let (delimiter, close) = match lexer.next()? {
+            Token::ParensLeft => (Delimiter::Parens, Token::ParensRight),
+            Token::BracketLeft => (Delimiter::Brackets, Token::BracketRight),
+            Token::BraceLeft => (Delimiter::Braces, Token::BraceRight),
+            found @ (Token::ParensRight | Token::BracketRight | Token::BraceRight) => {
+                return Err(ParseError::UnexpectedSymbolFound {
+                    expected: Token::ParensRight,
+                    found,
+                    span: lexer.span(),
+                })
+            }
+            token => {
+                return Ok(TokenTree::Token {
+                    token,
+                    span: lexer.span().into(),
+                })
+            }
+        };
 
+        let mut trees = vec![];
         loop {
+            match lexer.peek() {
+                Some(token) if token == close => break,
+                Some(Token::ParensRight | Token::BracketRight | Token::BraceRight) => {
+                    let found = lexer.next()?;
+                    return Err(ParseError::UnexpectedSymbolFound {
+                        expected: close,
+                        found,
+                        span: lexer.span(),
+                    });
+                }
+                _ => trees.push(Self::parse_token_tree(lexer)?),
+            }
+        }
+
+        lexer.expect(close)?;
+        Ok(TokenTree::Group {
+            delimiter,
+            trees,
+            span: lexer.span_from(start).into(),
+        })
+    }
+
+    fn parse_value_declaration(
+        &self,
+        lexer: &mut Lexer,
+        start: usize,
+        doc: Option<String>,
+        attributes: Vec<Attribute>,
+    ) -> Result<ValueDeclaration, ParseError> {
+        let name = self.parse_id(lexer)?;
+        if let Some(Token::ParensLeft) = lexer.peek() {
             let clause = self.parse_function_clause(lexer)?;
-            clauses.push(clause);
-            if let Some(Token::Semicolon) = lexer.peek() {
+            let span = clause.span;
+            return Ok(ValueDeclaration {
+                doc,
+                attributes,
+                name,
+                value: Expression::new(ExpressionKind::Function(vec![clause]), span),
+                span: lexer.span_from(start).into(),
+            });
+        }
+
+        lexer.expect(Token::Equal)?;
+        let value_start = lexer.checkpoint();
+        let value = self.parse_expression(lexer).map_err(|err| match err {
+            ParseError::EOF { .. } | ParseError::ExpectedExpression { .. }
+                if lexer.checkpoint() == value_start =>
+            {
+                ParseError::MissingValueInValueDeclaration {
+                    span: lexer.span_from(start),
+                }
+            }
+            err => err,
+        })?;
+
+        Ok(ValueDeclaration {
+            doc,
+            attributes,
+            name,
+            value,
+            span: lexer.span_from(start).into(),
+        })
+    }
+
+    fn parse_struct_declaration(
+        &self,
+        lexer: &mut Lexer,
+        start: usize,
+        doc: Option<String>,
+        attributes: Vec<Attribute>,
+    ) -> Result<StructDeclaration, ParseError> {
+        lexer.expect(Token::Struct)?;
+        let name = self.parse_id(lexer)?;
+        lexer.expect(Token::BraceLeft)?;
+
+        let mut fields = vec![];
+        loop {
+            if let Some(Token::BraceRight) = lexer.peek() {
+                break;
+            }
+
+            let field_start = lexer.next_offset();
+            let doc = self.parse_doc_comments(lexer)?;
+            let attributes = self.parse_attributes(lexer)?;
+            let name = self.parse_id(lexer)?;
+            lexer.expect(Token::Colon)?;
+            let ty = self.parse_type(lexer)?;
+            fields.push(FieldDeclaration {
+                doc,
+                attributes,
+                name,
+                ty,
+                span: lexer.span_from(field_start).into(),
+            });
+
+            if let Some(Token::Comma) = lexer.peek() {
                 lexer.next()?;
                 continue;
             }
+
             break;
         }
 
-        Ok(Expression::Function(clauses))
-    }
-
-    fn parse_function_clause(&self, lexer: &mut Lexer) -> Result<FunClause, ParseError> {
-        let args = self.parse_function_args(lexer)?;
-        lexer.expect(Token::BraceLeft)?;
-        let body = self.parse_expression(lexer)?;
         lexer.expect(Token::BraceRight)?;
-        Ok(FunClause { args, body })
+        Ok(StructDeclaration {
+            doc,
+            attributes,
+            name,
+            fields,
+            span: lexer.span_from(start).into(),
+        })
     }
 
-    fn parse_function_call(&self, lexer: &mut Lexer, id: Id) -> Result<Expression, ParseError> {
-        lexer.expect(Token::ParensLeft)?;
+    fn parse_enum_declaration(
+        &self,
+        lexer: &mut Lexer,
+        start: usize,
+        doc: Option<String>,
+        attributes: Vec<Attribute>,
+    ) -> Result<EnumDeclaration, ParseError> {
+        lexer.expect(Token::Enum)?;
+        let name = self.parse_id(lexer)?;
+        lexer.expect(Token::BraceLeft)?;
 
-        let mut args = vec![];
+        let mut variants = vec![];
         loop {
-            if let Some(Token::ParensRight) = lexer.peek() {
+            if let Some(Token::BraceRight) = lexer.peek() {
                 break;
             }
 
-            let arg = self.parse_expression(lexer)?;
-            args.push(arg);
+            let variant_start = lexer.next_offset();
+            let doc = self.parse_doc_comments(lexer)?;
+            let attributes = self.parse_attributes(lexer)?;
+            let name = self.parse_id(lexer)?;
+            let args = match lexer.peek() {
+                Some(Token::ParensLeft) => {
+                    lexer.next()?;
+                    self.parse_type_list(lexer)?
+                }
+                _ => vec![],
+            };
+            variants.push(VariantDeclaration {
+                doc,
+                attributes,
+                name,
+                args,
+                span: lexer.span_from(variant_start).into(),
+            });
 
             if let Some(Token::Comma) = lexer.peek() {
                 lexer.next()?;
@@ -155,21 +568,44 @@ impl Parser {
             break;
         }
 
-        lexer.expect(Token::ParensRight)?;
-        Ok(Expression::Call { id, args })
+        lexer.expect(Token::BraceRight)?;
+        Ok(EnumDeclaration {
+            doc,
+            attributes,
+            name,
+            variants,
+            span: lexer.span_from(start).into(),
+        })
     }
 
-    fn parse_function_args(&self, lexer: &mut Lexer) -> Result<Vec<Pattern>, ParseError> {
-        lexer.expect(Token::ParensLeft)?;
-        let mut patterns = vec![];
+    fn parse_type(&self, lexer: &mut Lexer) -> Result<Type, ParseError> {
+        let start = lexer.next_offset();
+        let kind = match lexer.peek() {
+            Some(Token::ParensLeft) => {
+                lexer.next()?;
+                TypeKind::Tuple(self.parse_type_list(lexer)?)
+            }
+            Some(Token::BracketLeft) => {
+                lexer.next()?;
+                let ty = self.parse_type(lexer)?;
+                lexer.expect(Token::BracketRight)?;
+                TypeKind::List(Box::new(ty))
+            }
+            _ => TypeKind::Name(self.parse_id(lexer)?),
+        };
+
+        Ok(Type::new(kind, lexer.span_from(start).into()))
+    }
 
+    /// Parses comma-separated types up to and including a `)`.
+    fn parse_type_list(&self, lexer: &mut Lexer) -> Result<Vec<Type>, ParseError> {
+        let mut types = vec![];
         loop {
             if let Some(Token::ParensRight) = lexer.peek() {
                 break;
             }
 
-            let pattern = self.parse_pattern(lexer)?;
-            patterns.push(pattern);
+            types.push(self.parse_type(lexer)?);
 
             if let Some(Token::Comma) = lexer.peek() {
                 lexer.next()?;
@@ -180,83 +616,3004 @@ impl Parser {
         }
 
         lexer.expect(Token::ParensRight)?;
-        Ok(patterns)
+        Ok(types)
     }
 
-    fn parse_pattern(&self, lexer: &mut Lexer) -> Result<Pattern, ParseError> {
-        match lexer.peek() {
-            Some(Token::Id(_)) => {
-                let id = self.parse_id(lexer)?;
-                Ok(Pattern::Bind(id))
+    fn parse_id(&self, lexer: &mut Lexer) -> Result<Id, ParseError> {
+        match lexer.next()? {
+            Token::Id(id) => Ok(Id(id)),
+            token if token.keyword().is_some() => Err(ParseError::ReservedKeyword {
+                keyword: token.keyword().unwrap(),
+                span: lexer.span(),
+            }),
+            token => Err(ParseError::UnexpectedSymbolFound {
+                expected: Token::Id("some_id".to_string()),
+                found: token,
+                span: lexer.span(),
+            }),
+        }
+    }
+
+    fn parse_expression(&self, lexer: &mut Lexer) -> Result<Expression, ParseError> {
+        self.parse_binary_expression(lexer, 0)
+    }
+
+    /// Precedence climbing over the table in [binary_operator]: keep folding
+    /// operators into `lhs` for as long as they bind at least as tight as
+    /// `min_precedence`.
+    fn parse_binary_expression(
+        &self,
+        lexer: &mut Lexer,
+        min_precedence: u8,
+    ) -> Result<Expression, ParseError> {
+        let mut lhs = self.parse_unary_expression(lexer)?;
+        let mut last_non_associative = None;
+
+        while let Some(token) = lexer.peek() {
+            let Some((op, precedence, associativity)) = binary_operator(&token) else {
+                break;
+            };
+
+            if precedence < min_precedence || (op == BinaryOp::Sub && lexer.next_starts_line()) {
+                break;
+            }
+
+            lexer.next()?;
+
+            if associativity == Associativity::None {
+                if last_non_associative == Some(precedence) {
+                    return Err(ParseError::NonAssociativeOperator {
+                        found: token,
+                        span: lexer.span(),
+                    });
+                }
+                last_non_associative = Some(precedence);
             }
-            Some(token) => Err(ParseError::ExpectedPattern { found: token }),
-            None => Err(ParseError::EOF),
+
+            let rhs = self.parse_binary_expression(lexer, precedence + 1)?;
+            let span = lhs.span.to(rhs.span);
+            let kind = ExpressionKind::BinaryOp {
+                op,
+                lhs: Box::new(lhs),
+                rhs: Box::new(rhs),
+            };
+            lhs = Expression::new(kind, span);
         }
+
+        Ok(lhs)
     }
-}
 
-mod tests {
-    use crate::token::Token;
+    fn parse_unary_expression(&self, lexer: &mut Lexer) -> Result<Expression, ParseError> {
+        let start = lexer.next_offset();
+        let op = match lexer.peek() {
+            Some(Token::Minus) => UnaryOp::Neg,
+            Some(Token::Bang) => UnaryOp::Not,
+            _ => return self.parse_postfix_expression(lexer),
+        };
+        lexer.next()?;
 
-    use super::*;
+        // a minus right before a number is folded into a negative literal,
+        // which postfix operators then apply to like to any other literal
+        let literal = match (op, lexer.peek()) {
+            (UnaryOp::Neg, Some(Token::Number(n))) => {
+                lexer.next()?;
+                let span = lexer.span_from(start);
+                let n = integer(lexer, 0i64.checked_sub_unsigned(n), span)?;
+                Some(Expression::new(ExpressionKind::Integer(n), span.into()))
+            }
+            (UnaryOp::Neg, Some(Token::Float(f))) => {
+                lexer.next()?;
+                let span = lexer.span_from(start).into();
+                Some(Expression::new(ExpressionKind::Float(-f), span))
+            }
+            _ => None,
+        };
+        if let Some(literal) = literal {
+            return self.parse_postfix_operators(lexer, literal);
+        }
 
-    #[test]
-    fn parse_empty_module() {
-        let mut parser = Parser::from_string("test_module", "");
-        let module = parser.parse().unwrap();
+        let expr = self.parse_unary_expression(lexer)?;
+        let kind = ExpressionKind::UnaryOp {
+            op,
+            expr: Box::new(expr),
+        };
+        Ok(Expression::new(kind, lexer.span_from(start).into()))
+    }
 
-        assert_eq!(module.name, Id("test_module".to_string()));
-        assert_eq!(module.items.len(), 0);
-        assert_eq!(module.items, vec![]);
-        assert_eq!(parser.diagnostics.len(), 0);
+    /// Parses chains of field accesses and method calls, like
+    /// `user.address.city` or `names.sort().first()`.
+    fn parse_postfix_expression(&self, lexer: &mut Lexer) -> Result<Expression, ParseError> {
+        let expr = self.parse_primary_expression(lexer)?;
+        self.parse_postfix_operators(lexer, expr)
     }
 
-    #[test]
-    fn parse_declaration_missing_equals() {
-        let mut parser = Parser::from_string(
-            "test_module",
-            r#"
-                Name ? "Q-Lang"
-            "#,
-        );
-        let _ = parser.parse().unwrap();
+    /// Parses the calls, field accesses and method calls that follow `expr`.
+    fn parse_postfix_operators(
+        &self,
+        lexer: &mut Lexer,
+        mut expr: Expression,
+    ) -> Result<Expression, ParseError> {
+        loop {
+            match lexer.peek() {
+                Some(Token::ParensLeft) if !lexer.next_starts_line() => {
+                    expr = self.parse_call(lexer, expr)?;
+                }
+                Some(Token::Dot) => {
+                    lexer.next()?;
+                    let field = self.parse_id(lexer)?;
+                    let called =
+                        lexer.peek() == Some(Token::ParensLeft) && !lexer.next_starts_line();
+                    expr = if called {
+                        lexer.next()?;
+                        let (args, _) = self.parse_expression_list(lexer, Token::ParensRight)?;
+                        let span = expr.span.to(lexer.span().into());
+                        let kind = ExpressionKind::MethodCall {
+                            receiver: Box::new(expr),
+                            method: field,
+                            args,
+                        };
+                        Expression::new(kind, span)
+                    } else {
+                        let span = expr.span.to(lexer.span().into());
+                        let kind = ExpressionKind::FieldAccess {
+                            expr: Box::new(expr),
+                            field,
+                        };
+                        Expression::new(kind, span)
+                    };
+                }
+                _ => break,
+            }
+        }
 
-        assert_eq!(parser.diagnostics.len(), 2);
-        assert_eq!(
-            parser.diagnostics,
-            vec![
-                ParseError::UnexpectedSymbolFound {
-                    expected: Token::Equal,
-                    found: Token::QuestionMark
-                },
-                ParseError::UnexpectedSymbolFound {
-                    expected: Token::Id("some_id".to_string()),
-                    found: Token::LiteralString("Q-Lang".to_string())
+        Ok(expr)
+    }
+
+    fn parse_primary_expression(&self, lexer: &mut Lexer) -> Result<Expression, ParseError> {
+        let start = lexer.next_offset();
+        let kind = match lexer.peek() {
+            Some(Token::Id(id)) if id == "true" || id == "false" => {
+                lexer.next()?;
+                ExpressionKind::Bool(id == "true")
+            }
+            Some(Token::Id(_)) => {
+                let id = self.parse_id(lexer)?;
+
+                match lexer.peek() {
+                    Some(Token::Colon) => return self.parse_remote(lexer, start, id),
+                    Some(Token::BraceLeft) if self.starts_struct_literal(lexer) => {
+                        lexer.next()?;
+                        let fields = self.parse_field_values(lexer)?;
+                        lexer.expect(Token::BraceRight)?;
+                        ExpressionKind::Struct {
+                            name: id,
+                            fields,
+                            module: vec![],
+                        }
+                    }
+                    _ => ExpressionKind::Variable(id),
                 }
-            ]
-        );
+            }
+            Some(Token::Atom(atom)) => {
+                lexer.next()?;
+                ExpressionKind::Atom(atom)
+            }
+            Some(Token::Number(n)) => {
+                lexer.next()?;
+                ExpressionKind::Integer(integer(lexer, i64::try_from(n).ok(), lexer.span())?)
+            }
+            Some(Token::Float(f)) => {
+                lexer.next()?;
+                ExpressionKind::Float(f)
+            }
+            Some(Token::Duration(duration)) => {
+                lexer.next()?;
+                ExpressionKind::Duration(duration)
+            }
+            Some(Token::LiteralString(str)) => {
+                lexer.next()?;
+                ExpressionKind::LiteralString(str)
+            }
+            Some(Token::InterpolatedString(raw)) => {
+                lexer.next()?;
+                // skip the opening backtick
+                let offset = lexer.span().offset() + 1;
+                self.parse_interpolated_string(&raw, offset)?
+            }
+            Some(Token::ParensLeft) if self.starts_function(lexer) => {
+                return self.parse_function(lexer)
+            }
+            Some(Token::ParensLeft) => {
+                lexer.next()?;
+                let (mut exprs, trailing_comma) =
+                    self.parse_expression_list(lexer, Token::ParensRight)?;
+                match exprs.len() {
+                    0 => ExpressionKind::Unit,
+                    // `(a)` is just `a` in parens, but `(a,)` is a tuple
+                    1 if !trailing_comma => exprs.remove(0).kind,
+                    _ => ExpressionKind::Tuple(exprs),
+                }
+            }
+            Some(Token::BracketLeft) => {
+                lexer.next()?;
+                let (exprs, _) = self.parse_expression_list(lexer, Token::BracketRight)?;
+                ExpressionKind::List(exprs)
+            }
+            Some(Token::Match) => return self.parse_match(lexer),
+            Some(Token::Spawn) => {
+                lexer.next()?;
+                let body = self.parse_unary_expression(lexer)?;
+                ExpressionKind::Spawn(Box::new(body))
+            }
+            Some(Token::Receive) => return self.parse_receive(lexer),
+            Some(Token::BraceLeft) => return self.parse_block(lexer),
+            Some(found) => {
+                return Err(ParseError::ExpectedExpression {
+                    found,
+                    span: lexer.peek_span().unwrap(),
+                })
+            }
+            None => {
+                return Err(ParseError::EOF {
+                    span: lexer.eof_span(),
+                })
+            }
+        };
+
+        Ok(Expression::new(kind, lexer.span_from(start).into()))
     }
 
-    #[test]
-    fn parse_module_with_declarations() {
-        let mut parser = Parser::from_string(
-            "test_module",
-            r#"
-                Name = "Q-Lang"
-            "#,
-        );
-        let module = parser.parse().unwrap();
+    /// Splits the raw contents of a backtick string into literal parts and
+    /// the expressions embedded between `{` and `}`. The string's contents
+    /// start `offset` bytes into the file.
+    fn parse_interpolated_string(
+        &self,
+        raw: &str,
+        offset: usize,
+    ) -> Result<ExpressionKind, ParseError> {
+        let mut parts = vec![];
+        let mut literal = String::new();
+        let mut chars = raw.char_indices();
+
+        while let Some((idx, char)) = chars.next() {
+            match char {
+                '\\' => match chars.next() {
+                    Some((_, 'n')) => literal.push('\n'),
+                    Some((_, 't')) => literal.push('\t'),
+                    Some((_, 'r')) => literal.push('\r'),
+                    Some((_, escaped @ ('\\' | '`' | '{' | '}'))) => literal.push(escaped),
+                    Some((_, found)) => {
+                        return Err(ParseError::UnknownEscapeSequence {
+                            found,
+                            span: (offset + idx, 1 + found.len_utf8()).into(),
+                        })
+                    }
+                    None => literal.push('\\'),
+                },
+                '{' => {
+                    let start = idx + 1;
+                    let Some(end) = interpolation_end(&raw[start..]).map(|end| start + end) else {
+                        return Err(ParseError::UnterminatedInterpolation {
+                            found: raw[start..].to_string(),
+                            span: (offset + idx, 1).into(),
+                        });
+                    };
+                    for (idx, _) in chars.by_ref() {
+                        if idx == end {
+                            break;
+                        }
+                    }
+
+                    if !literal.is_empty() {
+                        parts.push(StringPart::Literal(std::mem::take(&mut literal)));
+                    }
+                    let expr =
+                        self.parse_interpolated_expression(&raw[start..end], offset + start)?;
+                    parts.push(StringPart::Expression(expr));
+                }
+                _ => literal.push(char),
+            }
+        }
+
+        if !literal.is_empty() {
+            parts.push(StringPart::Literal(literal));
+        }
+
+        Ok(ExpressionKind::InterpolatedString(parts))
+    }
+
+    fn parse_interpolated_expression(
+        &self,
+        source: &str,
+        offset: usize,
+    ) -> Result<Expression, ParseError> {
+        let mut lexer = Lexer::from_source_at(source, offset);
+        let expr = self.parse_expression(&mut lexer)?;
+        if let Some(error) = lexer.take_errors().into_iter().next() {
+            return Err(ParseError::LexError(error));
+        }
+        match lexer.peek() {
+            None => Ok(expr),
+            Some(found) => Err(ParseError::UnexpectedSymbolFound {
+                expected: Token::BraceRight,
+                found,
+                span: lexer.peek_span().unwrap(),
+            }),
+        }
+    }
+
+    fn parse_function(&self, lexer: &mut Lexer) -> Result<Expression, ParseError> {
+        let start = lexer.next_offset();
+        let mut clauses = vec![];
+
+        loop {
+            let clause = self.parse_function_clause(lexer)?;
+            clauses.push(clause);
+            // a `;` that isn't followed by another clause ends a statement
+            let another_clause = lexer.peek() == Some(Token::Semicolon)
+                && lexer.lookahead(|lexer| {
+                    let _ = lexer.next();
+                    lexer.peek() == Some(Token::ParensLeft) && self.starts_function(lexer)
+                });
+            if another_clause {
+                lexer.next()?;
+                continue;
+            }
+            break;
+        }
+
+        let kind = ExpressionKind::Function(clauses);
+        Ok(Expression::new(kind, lexer.span_from(start).into()))
+    }
+
+    fn parse_function_clause(&self, lexer: &mut Lexer) -> Result<FunClause, ParseError> {
+        let start = lexer.next_offset();
+        let args = self.parse_function_args(lexer)?;
+        let guard = match lexer.peek() {
+            Some(Token::When) => Some(self.parse_guard(lexer)?),
+            _ => None,
+        };
+        let body = self.parse_block(lexer)?;
+        Ok(FunClause {
+            args,
+            guard,
+            body,
+            span: lexer.span_from(start).into(),
+        })
+    }
+
+    /// Parses the arguments of a call to `callee`, which can be any
+    /// expression, like in `make_adder(1)(2)`.
+    fn parse_call(&self, lexer: &mut Lexer, callee: Expression) -> Result<Expression, ParseError> {
+        lexer.expect(Token::ParensLeft)?;
+        let (args, _) = self.parse_expression_list(lexer, Token::ParensRight)?;
+        let span = callee.span.to(lexer.span().into());
+        let kind = ExpressionKind::Call {
+            callee: Box::new(callee),
+            args,
+        };
+        Ok(Expression::new(kind, span))
+    }
+
+    /// Parses the rest of a qualified name like `nat:next` or `math:nat:zero`.
+    fn parse_remote(
+        &self,
+        lexer: &mut Lexer,
+        start: usize,
+        first: Id,
+    ) -> Result<Expression, ParseError> {
+        let mut path = vec![first];
+        lexer.expect(Token::Colon)?;
+        let mut name = self.parse_id(lexer)?;
+        while let Some(Token::Colon) = lexer.peek() {
+            lexer.next()?;
+            let next = self.parse_id(lexer)?;
+            path.push(std::mem::replace(&mut name, next));
+        }
+
+        let kind = ExpressionKind::Remote(RemoteId { path, name });
+        Ok(Expression::new(kind, lexer.span_from(start).into()))
+    }
+
+    /// Parses comma-separated expressions up to and including `close`, and
+    /// whether the list ended with a trailing comma.
+    fn parse_expression_list(
+        &self,
+        lexer: &mut Lexer,
+        close: Token,
+    ) -> Result<(Vec<Expression>, bool), ParseError> {
+        self.with_brace_literals(true, |parser| {
+            parser.parse_expression_list_items(lexer, close)
+        })
+    }
+
+    fn parse_expression_list_items(
+        &self,
+        lexer: &mut Lexer,
+        close: Token,
+    ) -> Result<(Vec<Expression>, bool), ParseError> {
+        let mut exprs = vec![];
+        let mut trailing_comma = false;
+
+        loop {
+            if lexer.peek().as_ref() == Some(&close) {
+                break;
+            }
+
+            let expr = self.parse_expression(lexer)?;
+            exprs.push(expr);
+
+            trailing_comma = matches!(lexer.peek(), Some(Token::Comma));
+            if trailing_comma {
+                lexer.next()?;
+                continue;
+            }
+
+            break;
+        }
+
+        lexer.expect(close)?;
+        Ok((exprs, trailing_comma))
+    }
+
+    /// Whether the `(` up next opens the arguments of a function literal,
+    /// like `(a, b) { a + b }`, rather than unit, a tuple or an expression in
+    /// parens. Only functions have a `{` or a `when` guard right after the
+    /// closing `)`. Arguments are patterns, so we stop looking as soon as we
+    /// find a token that can't be part of one.
+    fn starts_function(&self, lexer: &mut Lexer) -> bool {
+        self.brace_literals.get() && lexer.lookahead(Self::skip_function_args)
+    }
+
+    /// Consumes what could be the arguments of a function literal, and
+    /// returns whether they're followed by a `{` or `when`.
+    fn skip_function_args(lexer: &mut Lexer) -> bool {
+        let mut depth = 0;
+        while let Ok(token) = lexer.next() {
+            match token {
+                Token::ParensLeft | Token::BracketLeft | Token::BraceLeft => depth += 1,
+                Token::ParensRight if depth == 1 => {
+                    return matches!(lexer.peek(), Some(Token::BraceLeft | Token::When))
+                }
+                Token::ParensRight | Token::BracketRight | Token::BraceRight if depth > 1 => {
+                    depth -= 1
+                }
+                Token::Id(_)
+                | Token::As
+                | Token::Comma
+                | Token::Colon
+                | Token::Pipe
+                | Token::Minus
+                | Token::Number(_)
+                | Token::Float(_)
+                | Token::Duration(_)
+                | Token::Atom(_)
+                | Token::LiteralString(_) => (),
+                // so that a keyword used as an argument gets a helpful error
+                token if token.keyword().is_some() => (),
+                _ => return false,
+            }
+        }
+        false
+    }
+
+    /// Parses the `name: value, other` fields of a struct literal or update,
+    /// up to but not including the closing `}`. A field without a value
+    /// takes the value of the variable with the same name.
+    fn parse_field_values(&self, lexer: &mut Lexer) -> Result<Vec<(Id, Expression)>, ParseError> {
+        let mut fields = vec![];
+
+        loop {
+            if let Some(Token::BraceRight) = lexer.peek() {
+                break;
+            }
+
+            let start = lexer.next_offset();
+            let field = self.parse_id(lexer)?;
+            let value = match lexer.peek() {
+                Some(Token::Colon) => {
+                    lexer.next()?;
+                    self.parse_expression(lexer)?
+                }
+                _ => Expression::new(
+                    ExpressionKind::Variable(field.clone()),
+                    lexer.span_from(start).into(),
+                ),
+            };
+            fields.push((field, value));
+
+            if let Some(Token::Comma) = lexer.peek() {
+                lexer.next()?;
+                continue;
+            }
+
+            break;
+        }
+
+        Ok(fields)
+    }
+
+    /// Whether the `{` up next opens the fields of a struct literal rather
+    /// than a block that follows a name, which it does when it's on the same
+    /// line as the name and is empty or starts with a field like `name:`,
+    /// `name,` or `name }`.
+    fn starts_struct_literal(&self, lexer: &mut Lexer) -> bool {
+        if !self.brace_literals.get() || lexer.next_starts_line() {
+            return false;
+        }
+        lexer.lookahead(|lexer| {
+            let _ = lexer.next();
+            match lexer.next() {
+                Ok(Token::BraceRight) => true,
+                Ok(Token::Id(_)) => matches!(
+                    lexer.peek(),
+                    Some(Token::Colon | Token::Comma | Token::BraceRight)
+                ),
+                _ => false,
+            }
+        })
+    }
+
+    /// Runs `f` with struct and function literals allowed or not, and
+    /// restores the previous setting afterwards.
+    fn with_brace_literals<T>(&self, allowed: bool, f: impl FnOnce(&Self) -> T) -> T {
+        let previous = self.brace_literals.replace(allowed);
+        let result = f(self);
+        self.brace_literals.set(previous);
+        result
+    }
+
+    /// Whether the block we just opened is a struct update like
+    /// `{ user | name: "b" }`, that is, whether its first expression is
+    /// followed by a `|` that isn't nested in other brackets. The expression
+    /// ends at a `;`, a binding, or a line break that isn't followed by the
+    /// `|`, so we never look past the first statement of a block.
+    fn starts_struct_update(&self, lexer: &mut Lexer) -> bool {
+        let mut depth = 0;
+        let mut n = 0;
+        while let Some(token) = lexer.peek_nth(n) {
+            if depth == 0 && n > 0 && token != Token::Pipe && lexer.nth_starts_line(n) {
+                return false;
+            }
+            match token {
+                Token::ParensLeft | Token::BracketLeft | Token::BraceLeft => depth += 1,
+                Token::ParensRight | Token::BracketRight | Token::BraceRight if depth == 0 => {
+                    return false
+                }
+                Token::ParensRight | Token::BracketRight | Token::BraceRight => depth -= 1,
+                Token::Pipe if depth == 0 => return true,
+                Token::Semicolon | Token::Equal | Token::LeftArrow if depth == 0 => return false,
+                _ => (),
+            }
+            n += 1;
+        }
+        false
+    }
+
+    /// Parses `{ statement; ... }`. Statements are separated by semicolons
+    /// or line breaks, so two of them can't share a line without a `;`.
+    /// A block holding a single expression is just that expression.
+    fn parse_block(&self, lexer: &mut Lexer) -> Result<Expression, ParseError> {
+        self.with_brace_literals(true, |parser| parser.parse_block_or_update(lexer))
+    }
+
+    fn parse_block_or_update(&self, lexer: &mut Lexer) -> Result<Expression, ParseError> {
+        let start = lexer.next_offset();
+        lexer.expect(Token::BraceLeft)?;
+
+        if self.starts_struct_update(lexer) {
+            let expr = self.parse_expression(lexer)?;
+            lexer.expect(Token::Pipe)?;
+            let fields = self.parse_field_values(lexer)?;
+            lexer.expect(Token::BraceRight)?;
+            let kind = ExpressionKind::StructUpdate {
+                expr: Box::new(expr),
+                fields,
+            };
+            return Ok(Expression::new(kind, lexer.span_from(start).into()));
+        }
+
+        let mut statements = vec![];
+        loop {
+            while let Some(Token::Semicolon) = lexer.peek() {
+                lexer.next()?;
+            }
+
+            // statements can't be documented, so doc comments in a block are
+            // skipped with a warning instead of failing the whole item
+            if let Some(Token::DocComment(_)) = lexer.peek() {
+                let doc_start = lexer.next_offset();
+                while let Some(Token::DocComment(_)) = lexer.peek() {
+                    lexer.next()?;
+                }
+                self.report(ParseError::DanglingDocComment {
+                    span: lexer.span_from(doc_start),
+                });
+                continue;
+            }
+
+            if let Some(Token::BraceRight) = lexer.peek() {
+                break;
+            }
+
+            statements.push(self.parse_statement(lexer)?);
+
+            match lexer.peek() {
+                Some(Token::Semicolon | Token::BraceRight) | None => (),
+                Some(found) if !lexer.next_starts_line() => {
+                    return Err(ParseError::MissingStatementSeparator {
+                        found,
+                        span: lexer.peek_span().unwrap(),
+                    })
+                }
+                Some(_) => (),
+            }
+        }
+
+        if statements.is_empty() {
+            return Err(ParseError::ExpectedExpression {
+                found: Token::BraceRight,
+                span: lexer.peek_span().unwrap(),
+            });
+        }
+
+        lexer.expect(Token::BraceRight)?;
+
+        if statements.len() == 1 && !matches!(statements[0].kind, ExpressionKind::Let { .. }) {
+            return Ok(statements.remove(0));
+        }
+
+        let kind = ExpressionKind::Block(statements);
+        Ok(Expression::new(kind, lexer.span_from(start).into()))
+    }
+
+    fn parse_statement(&self, lexer: &mut Lexer) -> Result<Expression, ParseError> {
+        let start = lexer.next_offset();
+        let kind = match (lexer.peek(), lexer.peek_nth(1)) {
+            (Some(Token::Mut), _) => {
+                lexer.next()?;
+                let name = self.parse_id(lexer)?;
+                lexer.expect(Token::Equal)?;
+                ExpressionKind::Let {
+                    name,
+                    value: Box::new(self.parse_expression(lexer)?),
+                    mutable: true,
+                }
+            }
+            (Some(Token::Id(_)), Some(Token::Equal)) => {
+                let name = self.parse_id(lexer)?;
+                lexer.expect(Token::Equal)?;
+                ExpressionKind::Let {
+                    name,
+                    value: Box::new(self.parse_expression(lexer)?),
+                    mutable: false,
+                }
+            }
+            (Some(Token::Id(_)), Some(Token::LeftArrow)) => {
+                let name = self.parse_id(lexer)?;
+                lexer.expect(Token::LeftArrow)?;
+                ExpressionKind::Assign {
+                    name,
+                    value: Box::new(self.parse_expression(lexer)?),
+                }
+            }
+            _ => return self.parse_expression(lexer),
+        };
+
+        Ok(Expression::new(kind, lexer.span_from(start).into()))
+    }
+
+    /// Parses `match expr { pattern => expr, ... }`. Arms may be separated
+    /// by commas, but since whitespace is insignificant they don't have to.
+    fn parse_match(&self, lexer: &mut Lexer) -> Result<Expression, ParseError> {
+        let start = lexer.next_offset();
+        lexer.expect(Token::Match)?;
+        let expr = self.with_brace_literals(false, |parser| parser.parse_expression(lexer))?;
+        let arms = self.parse_match_arms(lexer)?;
+        let kind = ExpressionKind::Match {
+            expr: Box::new(expr),
+            arms,
+        };
+        Ok(Expression::new(kind, lexer.span_from(start).into()))
+    }
+
+    /// Parses `receive { arms }` and the optional `after timeout { body }`.
+    fn parse_receive(&self, lexer: &mut Lexer) -> Result<Expression, ParseError> {
+        let start = lexer.next_offset();
+        lexer.expect(Token::Receive)?;
+        let arms = self.parse_match_arms(lexer)?;
+        if arms.is_empty() {
+            return Err(ParseError::EmptyReceive {
+                span: lexer.span_from(start),
+            });
+        }
+
+        let after = match lexer.peek() {
+            Some(Token::After) => {
+                let after_start = lexer.next_offset();
+                lexer.next()?;
+                let timeout =
+                    self.with_brace_literals(false, |parser| parser.parse_expression(lexer))?;
+                let body = self.parse_block(lexer)?;
+                Some(Box::new(ReceiveTimeout {
+                    timeout,
+                    body,
+                    span: lexer.span_from(after_start).into(),
+                }))
+            }
+            _ => None,
+        };
+
+        let kind = ExpressionKind::Receive { arms, after };
+        Ok(Expression::new(kind, lexer.span_from(start).into()))
+    }
+
+    /// Parses `{ pattern => expr, ... }`, where the commas are optional.
+    fn parse_match_arms(&self, lexer: &mut Lexer) -> Result<Vec<MatchArm>, ParseError> {
+        lexer.expect(Token::BraceLeft)?;
+
+        let mut arms = vec![];
+        loop {
+            if let Some(Token::BraceRight) = lexer.peek() {
+                break;
+            }
+
+            arms.push(self.parse_match_arm(lexer)?);
+
+            if let Some(Token::Comma) = lexer.peek() {
+                lexer.next()?;
+            }
+        }
+
+        lexer.expect(Token::BraceRight)?;
+        Ok(arms)
+    }
+
+    fn parse_match_arm(&self, lexer: &mut Lexer) -> Result<MatchArm, ParseError> {
+        let start = lexer.next_offset();
+        let pattern = self.parse_pattern(lexer)?;
+        let guard = match lexer.peek() {
+            Some(Token::When) => Some(self.parse_guard(lexer)?),
+            _ => None,
+        };
+        lexer.expect(Token::FatArrow)?;
+        let body = self.parse_expression(lexer)?;
+        Ok(MatchArm {
+            pattern,
+            guard,
+            body,
+            span: lexer.span_from(start).into(),
+        })
+    }
+
+    /// Parses `when condition`. The condition can't be a struct literal, as
+    /// the `{` that follows a function clause guard opens its body.
+    fn parse_guard(&self, lexer: &mut Lexer) -> Result<Expression, ParseError> {
+        lexer.expect(Token::When)?;
+        self.with_brace_literals(false, |parser| parser.parse_expression(lexer))
+    }
+
+    fn parse_function_args(&self, lexer: &mut Lexer) -> Result<Vec<Pattern>, ParseError> {
+        let (patterns, _) = self.parse_pattern_list(lexer)?;
+        Ok(patterns)
+    }
+
+    /// Parses a parenthesized, comma-separated list of patterns, and whether
+    /// the list ended with a trailing comma.
+    fn parse_pattern_list(&self, lexer: &mut Lexer) -> Result<(Vec<Pattern>, bool), ParseError> {
+        lexer.expect(Token::ParensLeft)?;
+        let mut patterns = vec![];
+        let mut trailing_comma = false;
+
+        loop {
+            if let Some(Token::ParensRight) = lexer.peek() {
+                break;
+            }
+
+            let pattern = self.parse_pattern(lexer)?;
+            patterns.push(pattern);
+
+            trailing_comma = matches!(lexer.peek(), Some(Token::Comma));
+            if trailing_comma {
+                lexer.next()?;
+                continue;
+            }
+
+            break;
+        }
+
+        lexer.expect(Token::ParensRight)?;
+        Ok((patterns, trailing_comma))
+    }
+
+    /// Parses `[a, b]`, `[head | tail]` and `[]` into `Cons` and `Nil`
+    /// patterns.
+    fn parse_list_pattern(&self, lexer: &mut Lexer) -> Result<Pattern, ParseError> {
+        let start = lexer.next_offset();
+        lexer.expect(Token::BracketLeft)?;
+
+        let mut heads = vec![];
+        let mut tail = None;
+        loop {
+            if let Some(Token::BracketRight) = lexer.peek() {
+                break;
+            }
+
+            heads.push(self.parse_pattern(lexer)?);
+
+            match lexer.peek() {
+                Some(Token::Comma) => {
+                    lexer.next()?;
+                }
+                Some(Token::Pipe) => {
+                    lexer.next()?;
+                    tail = Some(self.parse_pattern(lexer)?);
+                    break;
+                }
+                _ => break,
+            }
+        }
+
+        lexer.expect(Token::BracketRight)?;
+        let span: Span = lexer.span_from(start).into();
+
+        let tail = tail.unwrap_or_else(|| Pattern::new(PatternKind::Nil, span));
+        let list = heads.into_iter().rev().fold(tail, |tail, head| {
+            let span = Span::new(head.span.offset(), span.end() - head.span.offset());
+            let kind = PatternKind::Cons {
+                head: Box::new(head),
+                tail: Box::new(tail),
+            };
+            Pattern::new(kind, span)
+        });
+        Ok(Pattern::new(list.kind, span))
+    }
+
+    /// Parses the `{ name: pattern, other }` part of a struct pattern. A
+    /// field without a pattern binds the field's value to its own name.
+    fn parse_struct_pattern_fields(
+        &self,
+        lexer: &mut Lexer,
+    ) -> Result<Vec<(Id, Pattern)>, ParseError> {
+        lexer.expect(Token::BraceLeft)?;
+        let mut fields = vec![];
+
+        loop {
+            if let Some(Token::BraceRight) = lexer.peek() {
+                break;
+            }
+
+            let start = lexer.next_offset();
+            let field = self.parse_id(lexer)?;
+            let pattern = match lexer.peek() {
+                Some(Token::Colon) => {
+                    lexer.next()?;
+                    self.parse_pattern(lexer)?
+                }
+                _ => Pattern::new(
+                    PatternKind::Bind(field.clone()),
+                    lexer.span_from(start).into(),
+                ),
+            };
+            fields.push((field, pattern));
+
+            if let Some(Token::Comma) = lexer.peek() {
+                lexer.next()?;
+                continue;
+            }
+
+            break;
+        }
+
+        lexer.expect(Token::BraceRight)?;
+        Ok(fields)
+    }
+
+    fn parse_pattern(&self, lexer: &mut Lexer) -> Result<Pattern, ParseError> {
+        let start = lexer.next_offset();
+        let pattern = self.parse_primary_pattern(lexer)?;
+
+        if let Some(Token::As) = lexer.peek() {
+            lexer.next()?;
+            let alias = self.parse_id(lexer)?;
+            let kind = PatternKind::As {
+                pattern: Box::new(pattern),
+                alias,
+            };
+            return Ok(Pattern::new(kind, lexer.span_from(start).into()));
+        }
+
+        Ok(pattern)
+    }
+
+    fn parse_primary_pattern(&self, lexer: &mut Lexer) -> Result<Pattern, ParseError> {
+        let start = lexer.next_offset();
+        let kind = match lexer.peek() {
+            Some(Token::Id(id)) if id == "_" => {
+                lexer.next()?;
+                PatternKind::Wildcard
+            }
+            Some(Token::Id(id)) if id == "true" || id == "false" => {
+                lexer.next()?;
+                PatternKind::Literal(Literal::Bool(id == "true"))
+            }
+            Some(Token::Id(_)) => {
+                let mut module = vec![];
+                let mut name = self.parse_id(lexer)?;
+                while let Some(Token::Colon) = lexer.peek() {
+                    lexer.next()?;
+                    let next = self.parse_id(lexer)?;
+                    module.push(std::mem::replace(&mut name, next));
+                }
+                match lexer.peek() {
+                    Some(Token::ParensLeft) => PatternKind::Enum {
+                        module,
+                        name,
+                        args: self.parse_pattern_list(lexer)?.0,
+                    },
+                    Some(Token::BraceLeft) => PatternKind::Struct {
+                        module,
+                        name,
+                        fields: self.parse_struct_pattern_fields(lexer)?,
+                    },
+                    // a qualified name can't bind anything, so `flags:On` is
+                    // a variant without arguments
+                    _ if !module.is_empty() => PatternKind::Enum {
+                        module,
+                        name,
+                        args: vec![],
+                    },
+                    _ => PatternKind::Bind(name),
+                }
+            }
+            Some(Token::ParensLeft) => {
+                let (mut parts, trailing_comma) = self.parse_pattern_list(lexer)?;
+                // `(a)` is just `a` in parens, but `(a,)` is a tuple
+                if parts.len() == 1 && !trailing_comma {
+                    return Ok(parts.remove(0));
+                }
+                PatternKind::Tuple(parts)
+            }
+            Some(Token::BracketLeft) => return self.parse_list_pattern(lexer),
+            Some(Token::Minus) => {
+                lexer.next()?;
+                match lexer.next()? {
+                    Token::Number(n) => {
+                        let span = lexer.span_from(start);
+                        let n = integer(lexer, 0i64.checked_sub_unsigned(n), span)?;
+                        PatternKind::Literal(Literal::Integer(n))
+                    }
+                    Token::Float(f) => PatternKind::Literal(Literal::Float(-f)),
+                    found => {
+                        return Err(ParseError::ExpectedPattern {
+                            found,
+                            span: lexer.span(),
+                        })
+                    }
+                }
+            }
+            Some(Token::Number(n)) => {
+                lexer.next()?;
+                let n = integer(lexer, i64::try_from(n).ok(), lexer.span())?;
+                PatternKind::Literal(Literal::Integer(n))
+            }
+            Some(Token::Float(f)) => {
+                lexer.next()?;
+                PatternKind::Literal(Literal::Float(f))
+            }
+            Some(Token::Duration(duration)) => {
+                lexer.next()?;
+                PatternKind::Literal(Literal::Duration(duration))
+            }
+            Some(Token::Atom(atom)) => {
+                lexer.next()?;
+                PatternKind::Literal(Literal::Atom(atom))
+            }
+            Some(Token::LiteralString(str)) => {
+                lexer.next()?;
+                PatternKind::Literal(Literal::String(str))
+            }
+            Some(token) if token.keyword().is_some() => {
+                return Err(ParseError::ReservedKeyword {
+                    keyword: token.keyword().unwrap(),
+                    span: lexer.peek_span().unwrap(),
+                })
+            }
+            Some(found) => {
+                return Err(ParseError::ExpectedPattern {
+                    found,
+                    span: lexer.peek_span().unwrap(),
+                })
+            }
+            None => {
+                return Err(ParseError::EOF {
+                    span: lexer.eof_span(),
+                })
+            }
+        };
+
+        Ok(Pattern::new(kind, lexer.span_from(start).into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::token::Token;
+
+    use super::*;
+
+    #[test]
+    fn parse_empty_module() {
+        let mut parser = Parser::from_string("test_module", "");
+        let module = parser.parse().unwrap();
+
+        assert_eq!(module.name, Id("test_module".to_string()));
+        assert_eq!(module.items.len(), 0);
+        assert_eq!(module.items, vec![]);
+        assert_eq!(parser.diagnostics.len(), 0);
+    }
+
+    #[test]
+    fn parse_declaration_missing_equals() {
+        let mut parser = Parser::from_string(
+            "test_module",
+            r#"
+                Name ? "Q-Lang"
+            "#,
+        );
+        let _ = parser.parse().unwrap();
+
+        assert_eq!(parser.diagnostics.len(), 1);
+        assert_eq!(
+            parser.diagnostics,
+            vec![ParseError::UnexpectedSymbolFound {
+                expected: Token::Equal,
+                found: Token::QuestionMark,
+                span: (22, 1).into()
+            }]
+        );
+    }
+
+    #[test]
+    fn parse_recovers_at_the_next_declaration() {
+        let mut parser = Parser::from_string(
+            "test_module",
+            r#"
+                Name ? "Q-Lang"
+                Version = "0.1.0"
+                Broken = (a) { a + * b }
+                Print = (a, b) { a + b }
+                Keyword = )
+                Last = "last"
+            "#,
+        );
+        let module = parser.parse().unwrap();
+
+        assert_eq!(
+            parser.diagnostics,
+            vec![
+                ParseError::UnexpectedSymbolFound {
+                    expected: Token::Equal,
+                    found: Token::QuestionMark,
+                    span: (22, 1).into()
+                },
+                ParseError::ExpectedExpression {
+                    found: Token::Star,
+                    span: (102, 1).into()
+                },
+                ParseError::MissingValueInValueDeclaration {
+                    span: (165, 9).into(),
+                },
+            ]
+        );
+        let names: Vec<Id> = module
+            .items
+            .iter()
+            .map(|item| item.name().clone())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                Id("Version".to_string()),
+                Id("Print".to_string()),
+                Id("Last".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_module_with_declarations() {
+        let mut parser = Parser::from_string(
+            "test_module",
+            r#"
+                Name = "Q-Lang"
+            "#,
+        );
+        let module = parser.parse().unwrap();
+
+        assert_eq!(parser.diagnostics.len(), 0);
+        assert_eq!(parser.diagnostics, vec![]);
+        assert_eq!(module.name, Id("test_module".to_string()));
+        assert_eq!(module.items.len(), 1);
+        assert_eq!(
+            module.items.without_spans(),
+            vec![ModuleItem::ValueDeclaration(ValueDeclaration {
+                doc: None,
+                attributes: vec![],
+                name: Id("Name".to_string()),
+                value: ExpressionKind::LiteralString("Q-Lang".to_string()).into(),
+                span: Span::default()
+            })]
+        );
+    }
+
+    #[test]
+    fn parse_module_with_comments() {
+        let mut parser = Parser::from_string(
+            "test_module",
+            r#"
+                // not docs
+                /// The name of the language.
+                /// It's a good name.
+                Name = /* really */ "Q-Lang"
+
+                /* unrelated */
+                Version = "0.1.0" // for now
+            "#,
+        );
+        let module = parser.parse().unwrap();
+
+        assert_eq!(parser.diagnostics, vec![]);
+        assert_eq!(
+            module.items.without_spans(),
+            vec![
+                ModuleItem::ValueDeclaration(ValueDeclaration {
+                    doc: Some("The name of the language.\nIt's a good name.".to_string()),
+                    attributes: vec![],
+                    name: Id("Name".to_string()),
+                    value: ExpressionKind::LiteralString("Q-Lang".to_string()).into(),
+                    span: Span::default()
+                }),
+                ModuleItem::ValueDeclaration(ValueDeclaration {
+                    doc: None,
+                    attributes: vec![],
+                    name: Id("Version".to_string()),
+                    value: ExpressionKind::LiteralString("0.1.0".to_string()).into(),
+                    span: Span::default()
+                })
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_dangling_doc_comment() {
+        let mut parser = Parser::from_string(
+            "test_module",
+            r#"
+                Name = "Q-Lang"
+                /// docs for nothing
+            "#,
+        );
+        let module = parser.parse().unwrap();
+
+        assert_eq!(module.items.len(), 1);
+        assert_eq!(
+            parser.diagnostics,
+            vec![ParseError::DanglingDocComment {
+                span: (49, 20).into()
+            }]
+        );
+    }
+
+    #[test]
+    fn parse_doc_comment_in_a_block() {
+        let mut parser = Parser::from_string("test_module", "main = () {\n  /// not docs\n  1\n}");
+        let module = parser.parse().unwrap();
+
+        assert_eq!(
+            module.items.without_spans(),
+            vec![ModuleItem::ValueDeclaration(ValueDeclaration {
+                doc: None,
+                attributes: vec![],
+                name: Id("main".to_string()),
+                value: ExpressionKind::Function(vec![FunClause {
+                    args: vec![],
+                    guard: None,
+                    body: ExpressionKind::Integer(1).into(),
+                    span: Span::default(),
+                }])
+                .into(),
+                span: Span::default()
+            })]
+        );
+        assert_eq!(
+            parser.diagnostics,
+            vec![ParseError::DanglingDocComment {
+                span: (14, 12).into()
+            }]
+        );
+    }
+
+    #[test]
+    fn parse_module_with_lex_errors() {
+        let mut parser = Parser::from_string(
+            "test_module",
+            r#"
+Name = "Q-Lang" $
+Other = ~"Q"
+            "#,
+        );
+        let module = parser.parse().unwrap();
+
+        assert_eq!(
+            parser.diagnostics,
+            vec![
+                ParseError::LexError(LexError::UnexpectedCharacters {
+                    slice: "$".to_string(),
+                    span: (17, 1).into()
+                }),
+                ParseError::LexError(LexError::UnexpectedCharacters {
+                    slice: "~".to_string(),
+                    span: (27, 1).into()
+                }),
+            ]
+        );
+        assert_eq!(module.items.len(), 2);
+    }
+
+    #[test]
+    fn parse_keyword_as_declaration_name() {
+        let mut parser = Parser::from_string("test_module", "match = 1");
+        let _ = parser.parse().unwrap();
+
+        assert_eq!(
+            parser.diagnostics.first(),
+            Some(&ParseError::ReservedKeyword {
+                keyword: "match",
+                span: (0, 5).into()
+            })
+        );
+    }
+
+    #[test]
+    fn parse_keyword_as_function_argument() {
+        let mut parser = Parser::from_string("test_module", "f = (receive) { receive }");
+        let _ = parser.parse().unwrap();
+
+        assert_eq!(
+            parser.diagnostics.first(),
+            Some(&ParseError::ReservedKeyword {
+                keyword: "receive",
+                span: (5, 7).into()
+            })
+        );
+    }
+
+    fn parse_single_expression(source: &str) -> Result<Expression, ParseError> {
+        let parser = Parser::from_string("test_module", source);
+        let mut lexer = Lexer::from_source(source);
+        parser
+            .parse_expression(&mut lexer)
+            .map(StripSpans::without_spans)
+    }
+
+    fn var(name: &str) -> Box<Expression> {
+        Box::new(ExpressionKind::Variable(Id(name.to_string())).into())
+    }
+
+    #[test]
+    fn parse_binary_operator_precedence() {
+        assert_eq!(
+            parse_single_expression("a + b * c == d - e / f"),
+            Ok(ExpressionKind::BinaryOp {
+                op: BinaryOp::Equal,
+                lhs: Box::new(
+                    ExpressionKind::BinaryOp {
+                        op: BinaryOp::Add,
+                        lhs: var("a"),
+                        rhs: Box::new(
+                            ExpressionKind::BinaryOp {
+                                op: BinaryOp::Mul,
+                                lhs: var("b"),
+                                rhs: var("c"),
+                            }
+                            .into()
+                        ),
+                    }
+                    .into()
+                ),
+                rhs: Box::new(
+                    ExpressionKind::BinaryOp {
+                        op: BinaryOp::Sub,
+                        lhs: var("d"),
+                        rhs: Box::new(
+                            ExpressionKind::BinaryOp {
+                                op: BinaryOp::Div,
+                                lhs: var("e"),
+                                rhs: var("f"),
+                            }
+                            .into()
+                        ),
+                    }
+                    .into()
+                ),
+            }
+            .into())
+        );
+    }
+
+    #[test]
+    fn parse_binary_operators_are_left_associative() {
+        assert_eq!(
+            parse_single_expression("a - b - c || d && e"),
+            Ok(ExpressionKind::BinaryOp {
+                op: BinaryOp::Or,
+                lhs: Box::new(
+                    ExpressionKind::BinaryOp {
+                        op: BinaryOp::Sub,
+                        lhs: Box::new(
+                            ExpressionKind::BinaryOp {
+                                op: BinaryOp::Sub,
+                                lhs: var("a"),
+                                rhs: var("b"),
+                            }
+                            .into()
+                        ),
+                        rhs: var("c"),
+                    }
+                    .into()
+                ),
+                rhs: Box::new(
+                    ExpressionKind::BinaryOp {
+                        op: BinaryOp::And,
+                        lhs: var("d"),
+                        rhs: var("e"),
+                    }
+                    .into()
+                ),
+            }
+            .into())
+        );
+    }
+
+    #[test]
+    fn parse_comparisons_do_not_chain() {
+        assert_eq!(
+            parse_single_expression("a == b != c"),
+            Err(ParseError::NonAssociativeOperator {
+                found: Token::NotEqual,
+                span: (7, 2).into()
+            })
+        );
+        assert_eq!(
+            parse_single_expression("a < b == c"),
+            Ok(ExpressionKind::BinaryOp {
+                op: BinaryOp::Equal,
+                lhs: Box::new(
+                    ExpressionKind::BinaryOp {
+                        op: BinaryOp::LessThan,
+                        lhs: var("a"),
+                        rhs: var("b"),
+                    }
+                    .into()
+                ),
+                rhs: var("c"),
+            }
+            .into())
+        );
+    }
+
+    #[test]
+    fn parse_numeric_literals() {
+        assert_eq!(
+            parse_single_expression("1_000 + 0xff * -2.5e1 - 1000ms"),
+            Ok(ExpressionKind::BinaryOp {
+                op: BinaryOp::Sub,
+                lhs: Box::new(
+                    ExpressionKind::BinaryOp {
+                        op: BinaryOp::Add,
+                        lhs: Box::new(ExpressionKind::Integer(1000).into()),
+                        rhs: Box::new(
+                            ExpressionKind::BinaryOp {
+                                op: BinaryOp::Mul,
+                                lhs: Box::new(ExpressionKind::Integer(255).into()),
+                                rhs: Box::new(ExpressionKind::Float(-25.0).into()),
+                            }
+                            .into()
+                        ),
+                    }
+                    .into()
+                ),
+                rhs: Box::new(ExpressionKind::Duration(std::time::Duration::from_secs(1)).into()),
+            }
+            .into())
+        );
+    }
+
+    #[test]
+    fn parse_negative_integer_literals() {
+        assert_eq!(
+            parse_single_expression("-9223372036854775808"),
+            Ok(ExpressionKind::Integer(i64::MIN).into())
+        );
+        assert_eq!(
+            parse_single_expression("9223372036854775808"),
+            Err(ParseError::IntegerTooLarge {
+                slice: "9223372036854775808".to_string(),
+                span: (0, 19).into()
+            })
+        );
+        assert_eq!(
+            parse_single_expression("- 0x8000_0000_0000_0001"),
+            Err(ParseError::IntegerTooLarge {
+                slice: "- 0x8000_0000_0000_0001".to_string(),
+                span: (0, 23).into()
+            })
+        );
+        assert_eq!(
+            parse_single_expression("- -1"),
+            Ok(ExpressionKind::UnaryOp {
+                op: UnaryOp::Neg,
+                expr: Box::new(ExpressionKind::Integer(-1).into()),
+            }
+            .into())
+        );
+    }
+
+    #[test]
+    fn parse_number_too_large() {
+        let mut parser = Parser::from_string("test_module", "Big = 0xFFFF_FFFF_FFFF_FFFF_F");
+        let _ = parser.parse().unwrap();
+
+        assert_eq!(
+            parser.diagnostics.first(),
+            Some(&ParseError::LexError(LexError::NumberTooLarge {
+                slice: "0xFFFF_FFFF_FFFF_FFFF_F".to_string(),
+                span: (6, 23).into()
+            }))
+        );
+
+        let mut parser = Parser::from_string("test_module", "Digits = 0x_");
+        let _ = parser.parse().unwrap();
+
+        assert_eq!(
+            parser.diagnostics.first(),
+            Some(&ParseError::LexError(LexError::UnexpectedCharacters {
+                slice: "0x_".to_string(),
+                span: (9, 3).into()
+            }))
+        );
+    }
+
+    #[test]
+    fn parse_unicode_identifiers_and_atoms() {
+        assert_eq!(
+            parse_single_expression("valid?(user_v1) == #ok"),
+            Ok(ExpressionKind::BinaryOp {
+                op: BinaryOp::Equal,
+                lhs: Box::new(
+                    ExpressionKind::Call {
+                        callee: var("valid?"),
+                        args: vec![ExpressionKind::Variable(Id("user_v1".to_string())).into()]
+                    }
+                    .into()
+                ),
+                rhs: Box::new(ExpressionKind::Atom("ok".to_string()).into()),
+            }
+            .into())
+        );
+    }
+
+    #[test]
+    fn parse_unary_operators() {
+        assert_eq!(
+            parse_single_expression("!a && -b * c"),
+            Ok(ExpressionKind::BinaryOp {
+                op: BinaryOp::And,
+                lhs: Box::new(
+                    ExpressionKind::UnaryOp {
+                        op: UnaryOp::Not,
+                        expr: var("a"),
+                    }
+                    .into()
+                ),
+                rhs: Box::new(
+                    ExpressionKind::BinaryOp {
+                        op: BinaryOp::Mul,
+                        lhs: Box::new(
+                            ExpressionKind::UnaryOp {
+                                op: UnaryOp::Neg,
+                                expr: var("b"),
+                            }
+                            .into()
+                        ),
+                        rhs: var("c"),
+                    }
+                    .into()
+                ),
+            }
+            .into())
+        );
+    }
+
+    #[test]
+    fn parse_interpolated_string() {
+        let mut parser = Parser::from_string(
+            "test_module",
+            r"
+                Greeting = `Hello {name}, you are {age(name)}!\n`
+            ",
+        );
+        let module = parser.parse().unwrap();
+
+        assert_eq!(parser.diagnostics, vec![]);
+        assert_eq!(
+            module.items.without_spans(),
+            vec![ModuleItem::ValueDeclaration(ValueDeclaration {
+                doc: None,
+                attributes: vec![],
+                name: Id("Greeting".to_string()),
+                value: ExpressionKind::InterpolatedString(vec![
+                    StringPart::Literal("Hello ".to_string()),
+                    StringPart::Expression(ExpressionKind::Variable(Id("name".to_string())).into()),
+                    StringPart::Literal(", you are ".to_string()),
+                    StringPart::Expression(
+                        ExpressionKind::Call {
+                            callee: var("age"),
+                            args: vec![ExpressionKind::Variable(Id("name".to_string())).into()]
+                        }
+                        .into()
+                    ),
+                    StringPart::Literal("!\n".to_string()),
+                ])
+                .into(),
+                span: Span::default()
+            })]
+        );
+    }
+
+    #[test]
+    fn parse_interpolated_string_with_escaped_braces() {
+        let mut parser = Parser::from_string("test_module", r"Braces = `\{not {code}\}`");
+        let module = parser.parse().unwrap();
+
+        assert_eq!(parser.diagnostics, vec![]);
+        assert_eq!(
+            module.items.without_spans(),
+            vec![ModuleItem::ValueDeclaration(ValueDeclaration {
+                doc: None,
+                attributes: vec![],
+                name: Id("Braces".to_string()),
+                value: ExpressionKind::InterpolatedString(vec![
+                    StringPart::Literal("{not ".to_string()),
+                    StringPart::Expression(ExpressionKind::Variable(Id("code".to_string())).into()),
+                    StringPart::Literal("}".to_string()),
+                ])
+                .into(),
+                span: Span::default()
+            })]
+        );
+    }
+
+    #[test]
+    fn parse_interpolated_string_unterminated() {
+        let parser = Parser::from_string("test_module", "");
+        assert_eq!(
+            parser.parse_interpolated_string("Hello {name", 0),
+            Err(ParseError::UnterminatedInterpolation {
+                found: "name".to_string(),
+                span: (6, 1).into()
+            })
+        );
+    }
+
+    #[test]
+    fn parse_interpolated_string_with_braces_in_literals() {
+        let parser = Parser::from_string("test_module", "");
+        assert_eq!(
+            parser
+                .parse_interpolated_string(r#"a {"}"} b { /* } */ c }"#, 0)
+                .map(StripSpans::without_spans),
+            Ok(ExpressionKind::InterpolatedString(vec![
+                StringPart::Literal("a ".to_string()),
+                StringPart::Expression(ExpressionKind::LiteralString("}".to_string()).into()),
+                StringPart::Literal(" b ".to_string()),
+                StringPart::Expression(*var("c")),
+            ]))
+        );
+        assert_eq!(
+            parser.parse_interpolated_string(r#"a {"}" b"#, 0),
+            Err(ParseError::UnterminatedInterpolation {
+                found: r#""}" b"#.to_string(),
+                span: (2, 1).into()
+            })
+        );
+    }
+
+    #[test]
+    fn parse_nodes_carry_their_spans() {
+        let mut parser =
+            Parser::from_string("test_module", "Greet = (name) { `hi {name}` + f(1) }");
+        let module = parser.parse().unwrap();
+
+        let ModuleItem::ValueDeclaration(vd) = &module.items[0] else {
+            panic!("expected a value declaration, found {:?}", module.items[0])
+        };
+        assert_eq!((vd.span.offset(), vd.span.end()), (0, 37));
+
+        let ExpressionKind::Function(clauses) = &vd.value.kind else {
+            panic!("expected a function, found {:?}", vd.value)
+        };
+        assert_eq!((clauses[0].span.offset(), clauses[0].span.end()), (8, 37));
+        assert_eq!(clauses[0].args[0].span.offset(), 9);
+
+        let body = &clauses[0].body;
+        assert_eq!((body.span.offset(), body.span.end()), (17, 35));
+        let ExpressionKind::BinaryOp { lhs, rhs, .. } = &body.kind else {
+            panic!("expected a binary operation, found {:?}", body)
+        };
+        assert_eq!((lhs.span.offset(), lhs.span.end()), (17, 28));
+        assert_eq!((rhs.span.offset(), rhs.span.end()), (31, 35));
+
+        let ExpressionKind::InterpolatedString(parts) = &lhs.kind else {
+            panic!("expected an interpolated string, found {:?}", lhs)
+        };
+        let StringPart::Expression(name) = &parts[1] else {
+            panic!("expected an embedded expression, found {:?}", parts[1])
+        };
+        assert_eq!((name.span.offset(), name.span.end()), (22, 26));
+    }
+
+    #[test]
+    fn parse_match_expression() {
+        let expected: Expression = ExpressionKind::Match {
+            expr: Box::new(
+                ExpressionKind::Call {
+                    callee: var("f"),
+                    args: vec![*var("x")],
+                }
+                .into(),
+            ),
+            arms: vec![
+                MatchArm {
+                    pattern: PatternKind::Bind(Id("a".to_string())).into(),
+                    guard: None,
+                    body: ExpressionKind::BinaryOp {
+                        op: BinaryOp::Add,
+                        lhs: var("a"),
+                        rhs: Box::new(ExpressionKind::Integer(1).into()),
+                    }
+                    .into(),
+                    span: Span::default(),
+                },
+                MatchArm {
+                    pattern: PatternKind::Bind(Id("b".to_string())).into(),
+                    guard: None,
+                    body: *var("b"),
+                    span: Span::default(),
+                },
+            ],
+        }
+        .into();
+
+        assert_eq!(
+            parse_single_expression("match f(x) { a => a + 1, b => b }"),
+            Ok(expected.clone())
+        );
+        assert_eq!(
+            parse_single_expression("match f(x) {\n a => a + 1\n b => b\n }"),
+            Ok(expected)
+        );
+        assert_eq!(
+            parse_single_expression("match x {}"),
+            Ok(ExpressionKind::Match {
+                expr: var("x"),
+                arms: vec![]
+            }
+            .into())
+        );
+    }
+
+    #[test]
+    fn parse_match_arm_without_fat_arrow() {
+        assert_eq!(
+            parse_single_expression("match x { a -> a }"),
+            Err(ParseError::UnexpectedSymbolFound {
+                expected: Token::FatArrow,
+                found: Token::Arrow,
+                span: (12, 2).into()
+            })
+        );
+    }
+
+    #[test]
+    fn parse_block_bodies() {
+        let mut parser = Parser::from_string(
+            "test_module",
+            r#"
+                Count = (a) {
+                    print(a);
+                    x = 1
+                    x = x + 1
+                    x == 2
+                }
+            "#,
+        );
+        let module = parser.parse().unwrap();
+
+        assert_eq!(parser.diagnostics, vec![]);
+        let let_x = |value: Expression| -> Expression {
+            ExpressionKind::Let {
+                name: Id("x".to_string()),
+                value: Box::new(value),
+                mutable: false,
+            }
+            .into()
+        };
+        assert_eq!(
+            module.items.without_spans(),
+            vec![ModuleItem::ValueDeclaration(ValueDeclaration {
+                doc: None,
+                attributes: vec![],
+                name: Id("Count".to_string()),
+                value: ExpressionKind::Function(vec![FunClause {
+                    args: vec![bind("a")],
+                    guard: None,
+                    body: ExpressionKind::Block(vec![
+                        ExpressionKind::Call {
+                            callee: var("print"),
+                            args: vec![*var("a")]
+                        }
+                        .into(),
+                        let_x(ExpressionKind::Integer(1).into()),
+                        let_x(
+                            ExpressionKind::BinaryOp {
+                                op: BinaryOp::Add,
+                                lhs: var("x"),
+                                rhs: Box::new(ExpressionKind::Integer(1).into()),
+                            }
+                            .into()
+                        ),
+                        ExpressionKind::BinaryOp {
+                            op: BinaryOp::Equal,
+                            lhs: var("x"),
+                            rhs: Box::new(ExpressionKind::Integer(2).into()),
+                        }
+                        .into(),
+                    ])
+                    .into(),
+                    span: Span::default()
+                }])
+                .into(),
+                span: Span::default()
+            })]
+        );
+    }
+
+    #[test]
+    fn parse_blocks_as_expressions() {
+        assert_eq!(parse_single_expression("{ a }"), Ok(*var("a")));
+        assert_eq!(
+            parse_single_expression("match a { b => { c = b; c } }"),
+            Ok(ExpressionKind::Match {
+                expr: var("a"),
+                arms: vec![MatchArm {
+                    pattern: bind("b"),
+                    guard: None,
+                    body: ExpressionKind::Block(vec![
+                        ExpressionKind::Let {
+                            name: Id("c".to_string()),
+                            value: var("b"),
+                            mutable: false,
+                        }
+                        .into(),
+                        *var("c"),
+                    ])
+                    .into(),
+                    span: Span::default()
+                }]
+            }
+            .into())
+        );
+        assert_eq!(
+            parse_single_expression("{ x = 5\n -1\n x - 1 }"),
+            Ok(ExpressionKind::Block(vec![
+                ExpressionKind::Let {
+                    name: Id("x".to_string()),
+                    value: Box::new(ExpressionKind::Integer(5).into()),
+                    mutable: false,
+                }
+                .into(),
+                ExpressionKind::Integer(-1).into(),
+                ExpressionKind::BinaryOp {
+                    op: BinaryOp::Sub,
+                    lhs: var("x"),
+                    rhs: Box::new(ExpressionKind::Integer(1).into()),
+                }
+                .into(),
+            ])
+            .into())
+        );
+        assert_eq!(
+            parse_single_expression("{ ; }"),
+            Err(ParseError::ExpectedExpression {
+                found: Token::BraceRight,
+                span: (4, 1).into()
+            })
+        );
+    }
+
+    #[test]
+    fn parse_statements_on_one_line_need_a_separator() {
+        assert_eq!(
+            parse_single_expression("{ x.0 }"),
+            Err(ParseError::MissingStatementSeparator {
+                found: Token::Float(0.0),
+                span: (3, 2).into()
+            })
+        );
+        assert_eq!(
+            parse_single_expression("{ if x { 1 } }"),
+            Err(ParseError::MissingStatementSeparator {
+                found: Token::Id("x".to_string()),
+                span: (5, 1).into()
+            })
+        );
+        assert_eq!(
+            parse_single_expression("{ a; b\n c }"),
+            Ok(ExpressionKind::Block(vec![*var("a"), *var("b"), *var("c")]).into())
+        );
+    }
+
+    #[test]
+    fn parse_function_literals_in_blocks() {
+        let let_ = |name: &str, value: Expression| -> Expression {
+            ExpressionKind::Let {
+                name: Id(name.to_string()),
+                value: Box::new(value),
+                mutable: false,
+            }
+            .into()
+        };
+        let clause = |arg: Pattern, body: Expression| FunClause {
+            args: vec![arg],
+            guard: None,
+            body,
+            span: Span::default(),
+        };
+        assert_eq!(
+            parse_single_expression("{ inc = (x) { x + 1 }; inc(2) }"),
+            Ok(ExpressionKind::Block(vec![
+                let_(
+                    "inc",
+                    ExpressionKind::Function(vec![clause(
+                        bind("x"),
+                        ExpressionKind::BinaryOp {
+                            op: BinaryOp::Add,
+                            lhs: var("x"),
+                            rhs: Box::new(ExpressionKind::Integer(1).into()),
+                        }
+                        .into()
+                    )])
+                    .into()
+                ),
+                ExpressionKind::Call {
+                    callee: var("inc"),
+                    args: vec![ExpressionKind::Integer(2).into()],
+                }
+                .into(),
+            ])
+            .into())
+        );
+        assert_eq!(
+            parse_single_expression("{ f = (0) { 1 }; (n) { n }; f }"),
+            Ok(ExpressionKind::Block(vec![
+                let_(
+                    "f",
+                    ExpressionKind::Function(vec![
+                        clause(
+                            PatternKind::Literal(Literal::Integer(0)).into(),
+                            ExpressionKind::Integer(1).into()
+                        ),
+                        clause(bind("n"), *var("n")),
+                    ])
+                    .into()
+                ),
+                *var("f"),
+            ])
+            .into())
+        );
+    }
+
+    #[test]
+    fn parse_mutable_bindings_and_assignment() {
+        assert_eq!(
+            parse_single_expression("{ mut y = 1; y <- y + 2; y }"),
+            Ok(ExpressionKind::Block(vec![
+                ExpressionKind::Let {
+                    name: Id("y".to_string()),
+                    value: Box::new(ExpressionKind::Integer(1).into()),
+                    mutable: true,
+                }
+                .into(),
+                ExpressionKind::Assign {
+                    name: Id("y".to_string()),
+                    value: Box::new(
+                        ExpressionKind::BinaryOp {
+                            op: BinaryOp::Add,
+                            lhs: var("y"),
+                            rhs: Box::new(ExpressionKind::Integer(2).into()),
+                        }
+                        .into()
+                    ),
+                }
+                .into(),
+                *var("y"),
+            ])
+            .into())
+        );
+        assert_eq!(
+            parse_single_expression("{ mut y <- 1 }"),
+            Err(ParseError::UnexpectedSymbolFound {
+                expected: Token::Equal,
+                found: Token::LeftArrow,
+                span: (8, 2).into()
+            })
+        );
+    }
+
+    #[test]
+    fn parse_unit_tuple_and_list_literals() {
+        let int = |n| -> Expression { ExpressionKind::Integer(n).into() };
+
+        assert_eq!(
+            parse_single_expression("()"),
+            Ok(ExpressionKind::Unit.into())
+        );
+        assert_eq!(parse_single_expression("(a)"), Ok(*var("a")));
+        assert_eq!(
+            parse_single_expression("(a,)"),
+            Ok(ExpressionKind::Tuple(vec![*var("a")]).into())
+        );
+        assert_eq!(
+            parse_single_expression("(1, (2, 3))"),
+            Ok(ExpressionKind::Tuple(vec![
+                int(1),
+                ExpressionKind::Tuple(vec![int(2), int(3)]).into()
+            ])
+            .into())
+        );
+        assert_eq!(
+            parse_single_expression("[]"),
+            Ok(ExpressionKind::List(vec![]).into())
+        );
+        assert_eq!(
+            parse_single_expression("[a, [b],]"),
+            Ok(ExpressionKind::List(vec![
+                *var("a"),
+                ExpressionKind::List(vec![*var("b")]).into()
+            ])
+            .into())
+        );
+    }
+
+    #[test]
+    fn parse_parens_as_grouping_or_functions() {
+        assert_eq!(
+            parse_single_expression("(a + b) * c"),
+            Ok(ExpressionKind::BinaryOp {
+                op: BinaryOp::Mul,
+                lhs: Box::new(
+                    ExpressionKind::BinaryOp {
+                        op: BinaryOp::Add,
+                        lhs: var("a"),
+                        rhs: var("b"),
+                    }
+                    .into()
+                ),
+                rhs: var("c"),
+            }
+            .into())
+        );
+        assert_eq!(
+            parse_single_expression("((a), b) { a }"),
+            Ok(ExpressionKind::Function(vec![FunClause {
+                args: vec![bind("a"), bind("b")],
+                guard: None,
+                body: *var("a"),
+                span: Span::default()
+            }])
+            .into())
+        );
+        assert_eq!(
+            parse_single_expression("match (a, b) { (0, y) => y, _ => 9 }"),
+            Ok(ExpressionKind::Match {
+                expr: Box::new(ExpressionKind::Tuple(vec![*var("a"), *var("b")]).into()),
+                arms: vec![
+                    MatchArm {
+                        pattern: PatternKind::Tuple(vec![
+                            PatternKind::Literal(Literal::Integer(0)).into(),
+                            bind("y"),
+                        ])
+                        .into(),
+                        guard: None,
+                        body: *var("y"),
+                        span: Span::default(),
+                    },
+                    MatchArm {
+                        pattern: PatternKind::Wildcard.into(),
+                        guard: None,
+                        body: ExpressionKind::Integer(9).into(),
+                        span: Span::default(),
+                    },
+                ],
+            }
+            .into())
+        );
+        // `a + b` can't be an argument list, no matter what follows it
+        assert_eq!(
+            parse_single_expression("(a + b) {"),
+            Ok(ExpressionKind::BinaryOp {
+                op: BinaryOp::Add,
+                lhs: var("a"),
+                rhs: var("b"),
+            }
+            .into())
+        );
+    }
+
+    #[test]
+    fn parse_struct_and_enum_declarations() {
+        let mut parser = Parser::from_string(
+            "test_module",
+            r#"
+                /// A user.
+                struct User {
+                    /// What they go by.
+                    name: String,
+                    tags: [String],
+                }
+
+                enum Nat {
+                    Zero,
+                    /// One more than a natural.
+                    /// The last one, say.
+                    Succ(Nat),
+                    Pair((Nat, Nat))
+                }
+            "#,
+        );
+        let module = parser.parse().unwrap();
+
+        let ty = |name: &str| -> Type { TypeKind::Name(Id(name.to_string())).into() };
+        assert_eq!(parser.diagnostics, vec![]);
+        assert_eq!(
+            module.items.without_spans(),
+            vec![
+                ModuleItem::StructDeclaration(StructDeclaration {
+                    doc: Some("A user.".to_string()),
+                    attributes: vec![],
+                    name: Id("User".to_string()),
+                    fields: vec![
+                        FieldDeclaration {
+                            doc: Some("What they go by.".to_string()),
+                            attributes: vec![],
+                            name: Id("name".to_string()),
+                            ty: ty("String"),
+                            span: Span::default()
+                        },
+                        FieldDeclaration {
+                            doc: None,
+                            attributes: vec![],
+                            name: Id("tags".to_string()),
+                            ty: TypeKind::List(Box::new(ty("String"))).into(),
+                            span: Span::default()
+                        },
+                    ],
+                    span: Span::default()
+                }),
+                ModuleItem::EnumDeclaration(EnumDeclaration {
+                    doc: None,
+                    attributes: vec![],
+                    name: Id("Nat".to_string()),
+                    variants: vec![
+                        VariantDeclaration {
+                            doc: None,
+                            attributes: vec![],
+                            name: Id("Zero".to_string()),
+                            args: vec![],
+                            span: Span::default()
+                        },
+                        VariantDeclaration {
+                            doc: Some("One more than a natural.\nThe last one, say.".to_string()),
+                            attributes: vec![],
+                            name: Id("Succ".to_string()),
+                            args: vec![ty("Nat")],
+                            span: Span::default()
+                        },
+                        VariantDeclaration {
+                            doc: None,
+                            attributes: vec![],
+                            name: Id("Pair".to_string()),
+                            args: vec![TypeKind::Tuple(vec![ty("Nat"), ty("Nat")]).into()],
+                            span: Span::default()
+                        },
+                    ],
+                    span: Span::default()
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_function_declaration_shorthand() {
+        let source = r#"
+/// Squares a number.
+square(x) { x * x }
+next(Zero) { Succ(Zero) }
+next(n) { Succ(n) }
+zero = 0
+next(m) { m }
+"#;
+        let mut parser = Parser::from_string("nat", source);
+        let module = parser.parse().unwrap();
+
+        let clauses: Vec<(&str, usize)> = module
+            .items
+            .iter()
+            .map(|item| match item {
+                ModuleItem::ValueDeclaration(ValueDeclaration {
+                    name,
+                    value:
+                        Expression {
+                            kind: ExpressionKind::Function(clauses),
+                            ..
+                        },
+                    ..
+                }) => (name.0.as_str(), clauses.len()),
+                item => (item.name().0.as_str(), 0),
+            })
+            .collect();
+        assert_eq!(clauses, vec![("square", 1), ("next", 3), ("zero", 0)]);
+
+        let ModuleItem::ValueDeclaration(square) = &module.items[0] else {
+            panic!("expected a value declaration");
+        };
+        assert_eq!(square.doc, Some("Squares a number.".to_string()));
+        assert_eq!(
+            square.value.clone().without_spans(),
+            ExpressionKind::Function(vec![FunClause {
+                args: vec![bind("x")],
+                guard: None,
+                body: ExpressionKind::BinaryOp {
+                    op: BinaryOp::Mul,
+                    lhs: var("x"),
+                    rhs: var("x"),
+                }
+                .into(),
+                span: Span::default()
+            }])
+            .into()
+        );
+
+        let next = source.find("next").unwrap();
+        let split = source.rfind("next").unwrap();
+        assert_eq!(
+            parser.diagnostics,
+            vec![ParseError::SplitFunctionClauses {
+                name: "next".to_string(),
+                span: (split, "next(m) { m }".len()).into(),
+                first: (next, "next(Zero) { Succ(Zero) }\nnext(n) { Succ(n) }".len()).into(),
+            }]
+        );
+    }
+
+    #[test]
+    fn parse_duplicate_declarations() {
+        let source = "twice = (x) { x }\ntwice = (x) { x * 2 }\nhalf(x) { x }\nhalf = 0";
+        let mut parser = Parser::from_string("test_module", source);
+        let module = parser.parse().unwrap();
+
+        let names: Vec<&str> = module
+            .items
+            .iter()
+            .map(|item| item.name().0.as_str())
+            .collect();
+        assert_eq!(names, vec!["twice", "half"]);
+        let ModuleItem::ValueDeclaration(twice) = &module.items[0] else {
+            panic!("expected a value declaration");
+        };
+        assert!(
+            matches!(&twice.value.kind, ExpressionKind::Function(clauses) if clauses.len() == 1)
+        );
+
+        let at = |declaration: &str| -> SourceSpan {
+            (source.find(declaration).unwrap(), declaration.len()).into()
+        };
+        assert_eq!(
+            parser.diagnostics,
+            vec![
+                ParseError::DuplicateDeclaration {
+                    name: "twice".to_string(),
+                    span: at("twice = (x) { x * 2 }"),
+                    first: at("twice = (x) { x }"),
+                },
+                ParseError::DuplicateDeclaration {
+                    name: "half".to_string(),
+                    span: at("half = 0"),
+                    first: at("half(x) { x }"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_mod_declarations() {
+        let mut parser = Parser::from_string(
+            "math",
+            r#"
+                /// Natural numbers.
+                mod nat
+                one = () { 1 }
+            "#,
+        );
+        let module = parser.parse().unwrap();
 
-        assert_eq!(parser.diagnostics.len(), 0);
         assert_eq!(parser.diagnostics, vec![]);
-        assert_eq!(module.name, Id("test_module".to_string()));
-        assert_eq!(module.items.len(), 1);
         assert_eq!(
-            module.items,
-            vec![ModuleItem::ValueDeclaration(ValueDeclaration {
-                name: Id("Name".to_string()),
-                value: Expression::LiteralString("Q-Lang".to_string())
-            })]
+            module.items[0].clone().without_spans(),
+            ModuleItem::ModDeclaration(ModDeclaration {
+                doc: Some("Natural numbers.".to_string()),
+                attributes: vec![],
+                name: Id("nat".to_string()),
+                span: Span::default()
+            })
+        );
+        assert_eq!(module.items[1].name(), &Id("one".to_string()));
+    }
+
+    #[test]
+    fn module_is_named_after_the_file_stem() {
+        let mut parser = Parser::from_source(Path::new("math/nat.q"), "zero = 0".to_string());
+        assert_eq!(parser.parse().unwrap().name, Id("nat".to_string()));
+    }
+
+    #[test]
+    fn parse_recovers_after_a_broken_type_declaration() {
+        let mut parser = Parser::from_string(
+            "test_module",
+            r#"
+                struct User { name }
+                enum Role { Admin }
+            "#,
+        );
+        let module = parser.parse().unwrap();
+
+        assert_eq!(
+            parser.diagnostics,
+            vec![ParseError::UnexpectedSymbolFound {
+                expected: Token::Colon,
+                found: Token::BraceRight,
+                span: (36, 1).into()
+            }]
+        );
+        let names: Vec<Id> = module
+            .items
+            .iter()
+            .map(|item| item.name().clone())
+            .collect();
+        assert_eq!(names, vec![Id("Role".to_string())]);
+    }
+
+    #[test]
+    fn parse_remote_identifiers() {
+        let remote = |path: &[&str], name: &str| RemoteId {
+            path: path.iter().map(|module| Id(module.to_string())).collect(),
+            name: Id(name.to_string()),
+        };
+        assert_eq!(
+            parse_single_expression("nat:next(nat:zero())"),
+            Ok(ExpressionKind::Call {
+                callee: Box::new(ExpressionKind::Remote(remote(&["nat"], "next")).into()),
+                args: vec![ExpressionKind::Call {
+                    callee: Box::new(ExpressionKind::Remote(remote(&["nat"], "zero")).into()),
+                    args: vec![]
+                }
+                .into()]
+            }
+            .into())
+        );
+        assert_eq!(
+            parse_single_expression("math:nat:Zero"),
+            Ok(ExpressionKind::Remote(remote(&["math", "nat"], "Zero")).into())
+        );
+        assert_eq!(
+            parse_single_expression("User { name: nat:zero }"),
+            Ok(ExpressionKind::Struct {
+                name: Id("User".to_string()),
+                fields: vec![(
+                    Id("name".to_string()),
+                    ExpressionKind::Remote(remote(&["nat"], "zero")).into()
+                )],
+                module: vec![],
+            }
+            .into())
+        );
+    }
+
+    #[test]
+    fn parse_calls_of_any_expression() {
+        let call = |callee: Expression, args: Vec<Expression>| -> Expression {
+            ExpressionKind::Call {
+                callee: Box::new(callee),
+                args,
+            }
+            .into()
+        };
+        let int = |n: i64| -> Expression { ExpressionKind::Integer(n).into() };
+        assert_eq!(
+            parse_single_expression("make_adder(1)(2)"),
+            Ok(call(call(*var("make_adder"), vec![int(1)]), vec![int(2)]))
+        );
+        assert_eq!(
+            parse_single_expression(r#"(x) { x }("hi")"#),
+            Ok(call(
+                ExpressionKind::Function(vec![FunClause {
+                    args: vec![bind("x")],
+                    guard: None,
+                    body: *var("x"),
+                    span: Span::default()
+                }])
+                .into(),
+                vec![ExpressionKind::LiteralString("hi".to_string()).into()]
+            ))
+        );
+        assert_eq!(
+            parse_single_expression("{ f\n(a, b) }"),
+            Ok(ExpressionKind::Block(vec![
+                *var("f"),
+                ExpressionKind::Tuple(vec![*var("a"), *var("b")]).into()
+            ])
+            .into())
+        );
+        assert_eq!(
+            parse_single_expression("match x { A => 1\n(a, b) => 2 }"),
+            Ok(ExpressionKind::Match {
+                expr: var("x"),
+                arms: vec![
+                    MatchArm {
+                        pattern: bind("A"),
+                        guard: None,
+                        body: int(1),
+                        span: Span::default()
+                    },
+                    MatchArm {
+                        pattern: PatternKind::Tuple(vec![bind("a"), bind("b")]).into(),
+                        guard: None,
+                        body: int(2),
+                        span: Span::default()
+                    },
+                ]
+            }
+            .into())
+        );
+    }
+
+    #[test]
+    fn parse_postfix_chains() {
+        let field = |expr: Expression, field: &str| -> Expression {
+            ExpressionKind::FieldAccess {
+                expr: Box::new(expr),
+                field: Id(field.to_string()),
+            }
+            .into()
+        };
+        assert_eq!(
+            parse_single_expression("a.b.c(x).d"),
+            Ok(field(
+                ExpressionKind::MethodCall {
+                    receiver: Box::new(field(*var("a"), "b")),
+                    method: Id("c".to_string()),
+                    args: vec![*var("x")],
+                }
+                .into(),
+                "d"
+            ))
+        );
+        assert_eq!(
+            parse_single_expression(r#"std.io.print("hi")"#),
+            Ok(ExpressionKind::MethodCall {
+                receiver: Box::new(field(*var("std"), "io")),
+                method: Id("print".to_string()),
+                args: vec![ExpressionKind::LiteralString("hi".to_string()).into()]
+            }
+            .into())
+        );
+        assert_eq!(
+            parse_single_expression("f(x).len()"),
+            Ok(ExpressionKind::MethodCall {
+                receiver: Box::new(
+                    ExpressionKind::Call {
+                        callee: var("f"),
+                        args: vec![*var("x")],
+                    }
+                    .into()
+                ),
+                method: Id("len".to_string()),
+                args: vec![]
+            }
+            .into())
+        );
+    }
+
+    #[test]
+    fn parse_postfix_operators_on_negative_literals() {
+        let method_call = |receiver: Expression, method: &str| -> Expression {
+            ExpressionKind::MethodCall {
+                receiver: Box::new(receiver),
+                method: Id(method.to_string()),
+                args: vec![],
+            }
+            .into()
+        };
+        assert_eq!(
+            parse_single_expression("-1.abs()"),
+            Ok(method_call(ExpressionKind::Integer(-1).into(), "abs"))
+        );
+        assert_eq!(
+            parse_single_expression("-1.0.floor()"),
+            Ok(method_call(ExpressionKind::Float(-1.0).into(), "floor"))
+        );
+        assert_eq!(
+            parse_single_expression("-x.abs()"),
+            Ok(ExpressionKind::UnaryOp {
+                op: UnaryOp::Neg,
+                expr: Box::new(method_call(*var("x"), "abs")),
+            }
+            .into())
+        );
+    }
+
+    #[test]
+    fn parse_spawn_receive_and_send() {
+        let print = |name: &str| -> Expression {
+            ExpressionKind::Call {
+                callee: var("print"),
+                args: vec![*var(name)],
+            }
+            .into()
+        };
+        assert_eq!(
+            parse_single_expression("spawn { worker() }"),
+            Ok(ExpressionKind::Spawn(Box::new(
+                ExpressionKind::Call {
+                    callee: var("worker"),
+                    args: vec![]
+                }
+                .into()
+            ))
+            .into())
+        );
+        assert_eq!(
+            parse_single_expression(
+                "receive { SayHello(name) => print(name), _ => print(other) } after 1000ms { print(nobody) }"
+            ),
+            Ok(ExpressionKind::Receive {
+                arms: vec![
+                    MatchArm {
+                        pattern: PatternKind::Enum {
+                            module: vec![],
+                            name: Id("SayHello".to_string()),
+                            args: vec![bind("name")]
+                        }
+                        .into(),
+                        guard: None,
+                        body: print("name"),
+                        span: Span::default()
+                    },
+                    MatchArm {
+                        pattern: PatternKind::Wildcard.into(),
+                        guard: None,
+                        body: print("other"),
+                        span: Span::default()
+                    },
+                ],
+                after: Some(Box::new(ReceiveTimeout {
+                    timeout: ExpressionKind::Duration(std::time::Duration::from_millis(1000))
+                        .into(),
+                    body: print("nobody"),
+                    span: Span::default()
+                }))
+            }
+            .into())
+        );
+        assert_eq!(
+            parse_single_expression("receive { msg => msg }"),
+            Ok(ExpressionKind::Receive {
+                arms: vec![MatchArm {
+                    pattern: bind("msg"),
+                    guard: None,
+                    body: *var("msg"),
+                    span: Span::default()
+                }],
+                after: None
+            }
+            .into())
+        );
+        assert_eq!(
+            parse_single_expression("receive {}"),
+            Err(ParseError::EmptyReceive {
+                span: (0, 10).into()
+            })
+        );
+        // name resolution decides whether this sends a message
+        assert_eq!(
+            parse_single_expression("state.proc.send(msg)"),
+            Ok(ExpressionKind::MethodCall {
+                receiver: Box::new(
+                    ExpressionKind::FieldAccess {
+                        expr: var("state"),
+                        field: Id("proc".to_string()),
+                    }
+                    .into()
+                ),
+                method: Id("send".to_string()),
+                args: vec![*var("msg")],
+            }
+            .into())
+        );
+    }
+
+    #[test]
+    fn parse_guards_on_clauses_and_arms() {
+        let greater = |name: &str, n: i64| -> Expression {
+            ExpressionKind::BinaryOp {
+                op: BinaryOp::GreaterThan,
+                lhs: var(name),
+                rhs: Box::new(ExpressionKind::Integer(n).into()),
+            }
+            .into()
+        };
+        assert_eq!(
+            parse_single_expression("(n) when n > 0 { n }"),
+            Ok(ExpressionKind::Function(vec![FunClause {
+                args: vec![bind("n")],
+                guard: Some(greater("n", 0)),
+                body: *var("n"),
+                span: Span::default()
+            }])
+            .into())
+        );
+        assert_eq!(
+            parse_single_expression("match a { x when x > 1 => x, _ => a }"),
+            Ok(ExpressionKind::Match {
+                expr: var("a"),
+                arms: vec![
+                    MatchArm {
+                        pattern: bind("x"),
+                        guard: Some(greater("x", 1)),
+                        body: *var("x"),
+                        span: Span::default()
+                    },
+                    MatchArm {
+                        pattern: PatternKind::Wildcard.into(),
+                        guard: None,
+                        body: *var("a"),
+                        span: Span::default()
+                    },
+                ]
+            }
+            .into())
+        );
+        assert_eq!(
+            parse_single_expression("receive { msg when msg > 2 => msg }"),
+            Ok(ExpressionKind::Receive {
+                arms: vec![MatchArm {
+                    pattern: bind("msg"),
+                    guard: Some(greater("msg", 2)),
+                    body: *var("msg"),
+                    span: Span::default()
+                }],
+                after: None
+            }
+            .into())
+        );
+    }
+
+    #[test]
+    fn parse_struct_literals_access_and_updates() {
+        assert_eq!(
+            parse_single_expression(r#"User { name: "joe", age }.name"#),
+            Ok(ExpressionKind::FieldAccess {
+                expr: Box::new(
+                    ExpressionKind::Struct {
+                        name: Id("User".to_string()),
+                        fields: vec![
+                            (
+                                Id("name".to_string()),
+                                ExpressionKind::LiteralString("joe".to_string()).into()
+                            ),
+                            (Id("age".to_string()), *var("age")),
+                        ],
+                        module: vec![],
+                    }
+                    .into()
+                ),
+                field: Id("name".to_string()),
+            }
+            .into())
+        );
+        assert_eq!(
+            parse_single_expression("{ user.address | city: c, }"),
+            Ok(ExpressionKind::StructUpdate {
+                expr: Box::new(
+                    ExpressionKind::FieldAccess {
+                        expr: var("user"),
+                        field: Id("address".to_string()),
+                    }
+                    .into()
+                ),
+                fields: vec![(Id("city".to_string()), *var("c"))],
+            }
+            .into())
+        );
+        assert_eq!(
+            parse_single_expression("match user {}"),
+            Ok(ExpressionKind::Match {
+                expr: var("user"),
+                arms: vec![]
+            }
+            .into())
+        );
+        assert_eq!(
+            parse_single_expression("{ a\n { b <- 1 } }"),
+            Ok(ExpressionKind::Block(vec![
+                *var("a"),
+                ExpressionKind::Assign {
+                    name: Id("b".to_string()),
+                    value: Box::new(ExpressionKind::Integer(1).into()),
+                }
+                .into(),
+            ])
+            .into())
+        );
+        assert_eq!(
+            parse_single_expression("{ y = f\n { args }\n y }"),
+            Ok(ExpressionKind::Block(vec![
+                ExpressionKind::Let {
+                    name: Id("y".to_string()),
+                    value: var("f"),
+                    mutable: false,
+                }
+                .into(),
+                *var("args"),
+                *var("y"),
+            ])
+            .into())
+        );
+        assert_eq!(
+            parse_single_expression("{ user\n | name: \"b\" }"),
+            Ok(ExpressionKind::StructUpdate {
+                expr: var("user"),
+                fields: vec![(
+                    Id("name".to_string()),
+                    ExpressionKind::LiteralString("b".to_string()).into()
+                )],
+            }
+            .into())
+        );
+    }
+
+    #[test]
+    fn parse_attributes() {
+        let mut parser = Parser::from_string(
+            "test_module",
+            r#"
+                /// The entry point.
+                @cli
+                @std.doc(hidden, [1, 2], { a: b })
+                main = (args) { args }
+            "#,
+        );
+        let module = parser.parse().unwrap();
+
+        assert_eq!(parser.diagnostics, vec![]);
+        let ModuleItem::ValueDeclaration(vd) = &module.items[0] else {
+            panic!("expected a value declaration, found {:?}", module.items[0])
+        };
+        assert_eq!(vd.doc, Some("The entry point.".to_string()));
+
+        let token = |token| TokenTree::Token {
+            token,
+            span: Span::default(),
+        };
+        let group = |delimiter, trees| TokenTree::Group {
+            delimiter,
+            trees,
+            span: Span::default(),
+        };
+        let id = |name: &str| token(Token::Id(name.to_string()));
+        assert_eq!(
+            vd.attributes.clone().without_spans(),
+            vec![
+                Attribute {
+                    path: vec![Id("cli".to_string())],
+                    args: vec![],
+                    span: Span::default()
+                },
+                Attribute {
+                    path: vec![Id("std".to_string()), Id("doc".to_string())],
+                    args: vec![
+                        id("hidden"),
+                        token(Token::Comma),
+                        group(
+                            Delimiter::Brackets,
+                            vec![
+                                token(Token::Number(1)),
+                                token(Token::Comma),
+                                token(Token::Number(2))
+                            ]
+                        ),
+                        token(Token::Comma),
+                        group(
+                            Delimiter::Braces,
+                            vec![id("a"), token(Token::Colon), id("b")]
+                        ),
+                    ],
+                    span: Span::default()
+                },
+            ]
+        );
+        assert_eq!(vd.attributes[1].name(), "std.doc");
+        assert_eq!(
+            (vd.attributes[1].span.offset(), vd.attributes[1].span.end()),
+            (75, 109)
+        );
+    }
+
+    #[test]
+    fn parse_unbalanced_attribute_arguments() {
+        let mut parser = Parser::from_string(
+            "test_module",
+            r#"
+                @derive(Debug])
+                struct User { name: String }
+                Next = 1
+            "#,
+        );
+        let module = parser.parse().unwrap();
+
+        assert_eq!(
+            parser.diagnostics,
+            vec![ParseError::UnexpectedSymbolFound {
+                expected: Token::ParensRight,
+                found: Token::BracketRight,
+                span: (30, 1).into()
+            }]
+        );
+        let names: Vec<Id> = module
+            .items
+            .iter()
+            .map(|item| item.name().clone())
+            .collect();
+        assert_eq!(names, vec![Id("User".to_string()), Id("Next".to_string())]);
+    }
+
+    #[test]
+    fn parse_unclosed_attribute_arguments() {
+        let mut parser = Parser::from_string(
+            "test_module",
+            r#"
+                @derive(Debug
+                struct User { name: String }
+                @derive(Eq, (Ord) struct Id { id: Int }
+                @doc(
+                  hidden
+                )
+                Next = 1
+            "#,
+        );
+        let module = parser.parse().unwrap();
+
+        assert_eq!(
+            parser.diagnostics,
+            vec![
+                ParseError::UnclosedAttributeArguments {
+                    span: (17, 1).into()
+                },
+                ParseError::UnclosedAttributeArguments {
+                    span: (92, 1).into()
+                },
+            ]
+        );
+        let names: Vec<Id> = module
+            .items
+            .iter()
+            .map(|item| item.name().clone())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                Id("User".to_string()),
+                Id("Id".to_string()),
+                Id("Next".to_string())
+            ]
+        );
+        let ModuleItem::ValueDeclaration(next) = &module.items[2] else {
+            panic!("expected a value declaration");
+        };
+        assert_eq!(next.attributes[0].args.len(), 1);
+    }
+
+    fn parse_single_pattern(source: &str) -> Result<Pattern, ParseError> {
+        let parser = Parser::from_string("test_module", source);
+        let mut lexer = Lexer::from_source(source);
+        parser
+            .parse_pattern(&mut lexer)
+            .map(StripSpans::without_spans)
+    }
+
+    fn bind(name: &str) -> Pattern {
+        PatternKind::Bind(Id(name.to_string())).into()
+    }
+
+    fn cons(head: Pattern, tail: Pattern) -> Pattern {
+        PatternKind::Cons {
+            head: Box::new(head),
+            tail: Box::new(tail),
+        }
+        .into()
+    }
+
+    #[test]
+    fn parse_literal_patterns() {
+        assert_eq!(
+            parse_single_pattern(r#"(_, 0, -1, 2.5, 10ms, "s", #ok, true)"#),
+            Ok(PatternKind::Tuple(vec![
+                PatternKind::Wildcard.into(),
+                PatternKind::Literal(Literal::Integer(0)).into(),
+                PatternKind::Literal(Literal::Integer(-1)).into(),
+                PatternKind::Literal(Literal::Float(2.5)).into(),
+                PatternKind::Literal(Literal::Duration(std::time::Duration::from_millis(10)))
+                    .into(),
+                PatternKind::Literal(Literal::String("s".to_string())).into(),
+                PatternKind::Literal(Literal::Atom("ok".to_string())).into(),
+                PatternKind::Literal(Literal::Bool(true)).into(),
+            ])
+            .into())
+        );
+    }
+
+    #[test]
+    fn parse_tuple_and_list_patterns() {
+        assert_eq!(
+            parse_single_pattern("()"),
+            Ok(PatternKind::Tuple(vec![]).into())
+        );
+        assert_eq!(parse_single_pattern("(a)"), Ok(bind("a")));
+        assert_eq!(
+            parse_single_pattern("(a,)"),
+            Ok(PatternKind::Tuple(vec![bind("a")]).into())
+        );
+        assert_eq!(parse_single_pattern("[]"), Ok(PatternKind::Nil.into()));
+        assert_eq!(
+            parse_single_pattern("[a, b]"),
+            Ok(cons(bind("a"), cons(bind("b"), PatternKind::Nil.into())))
+        );
+        assert_eq!(
+            parse_single_pattern("[a, (b, c) | rest]"),
+            Ok(cons(
+                bind("a"),
+                cons(
+                    PatternKind::Tuple(vec![bind("b"), bind("c")]).into(),
+                    bind("rest")
+                )
+            ))
+        );
+    }
+
+    #[test]
+    fn parse_struct_enum_and_alias_patterns() {
+        assert_eq!(
+            parse_single_pattern("User { name: \"joe\", age } as user"),
+            Ok(PatternKind::As {
+                pattern: Box::new(
+                    PatternKind::Struct {
+                        module: vec![],
+                        name: Id("User".to_string()),
+                        fields: vec![
+                            (
+                                Id("name".to_string()),
+                                PatternKind::Literal(Literal::String("joe".to_string())).into()
+                            ),
+                            (Id("age".to_string()), bind("age")),
+                        ]
+                    }
+                    .into()
+                ),
+                alias: Id("user".to_string()),
+            }
+            .into())
+        );
+        assert_eq!(
+            parse_single_pattern("Succ(Succ(_))"),
+            Ok(PatternKind::Enum {
+                module: vec![],
+                name: Id("Succ".to_string()),
+                args: vec![PatternKind::Enum {
+                    module: vec![],
+                    name: Id("Succ".to_string()),
+                    args: vec![PatternKind::Wildcard.into()]
+                }
+                .into()]
+            }
+            .into())
+        );
+    }
+
+    #[test]
+    fn parse_qualified_patterns() {
+        let path =
+            |names: &[&str]| -> Vec<Id> { names.iter().map(|name| Id(name.to_string())).collect() };
+        assert_eq!(
+            parse_single_pattern("flags:On"),
+            Ok(PatternKind::Enum {
+                module: path(&["flags"]),
+                name: Id("On".to_string()),
+                args: vec![]
+            }
+            .into())
+        );
+        assert_eq!(
+            parse_single_pattern("math:nat:Succ(n)"),
+            Ok(PatternKind::Enum {
+                module: path(&["math", "nat"]),
+                name: Id("Succ".to_string()),
+                args: vec![bind("n")]
+            }
+            .into())
+        );
+        assert_eq!(
+            parse_single_pattern("geo:Point { x }"),
+            Ok(PatternKind::Struct {
+                module: path(&["geo"]),
+                name: Id("Point".to_string()),
+                fields: vec![(Id("x".to_string()), bind("x"))]
+            }
+            .into())
+        );
+        assert_eq!(
+            parse_single_pattern("flags:"),
+            Err(ParseError::EOF {
+                span: (6, 0).into()
+            })
+        );
+    }
+
+    #[test]
+    fn parse_invalid_patterns() {
+        assert_eq!(
+            parse_single_pattern("[a | b, c]"),
+            Err(ParseError::UnexpectedSymbolFound {
+                expected: Token::BracketRight,
+                found: Token::Comma,
+                span: (6, 1).into()
+            })
+        );
+        assert_eq!(
+            parse_single_pattern("x as as"),
+            Err(ParseError::ReservedKeyword {
+                keyword: "as",
+                span: (5, 2).into()
+            })
+        );
+        assert_eq!(parse_single_pattern("a + b"), Ok(bind("a")));
+        assert_eq!(
+            parse_single_pattern("-x"),
+            Err(ParseError::ExpectedPattern {
+                found: Token::Id("x".to_string()),
+                span: (1, 1).into()
+            })
         );
     }
 
@@ -275,13 +3632,19 @@ mod tests {
         assert_eq!(module.name, Id("test_module".to_string()));
         assert_eq!(module.items.len(), 1);
         assert_eq!(
-            module.items,
+            module.items.without_spans(),
             vec![ModuleItem::ValueDeclaration(ValueDeclaration {
+                doc: None,
+                attributes: vec![],
                 name: Id("Print".to_string()),
-                value: Expression::Function(vec![FunClause {
+                value: ExpressionKind::Function(vec![FunClause {
                     args: vec![],
-                    body: Expression::LiteralString("Hello".to_string())
+                    guard: None,
+                    body: ExpressionKind::LiteralString("Hello".to_string()).into(),
+                    span: Span::default()
                 }])
+                .into(),
+                span: Span::default()
             })]
         );
     }
@@ -305,33 +3668,45 @@ mod tests {
         assert_eq!(module.name, Id("test_module".to_string()));
         assert_eq!(module.items.len(), 1);
         assert_eq!(
-            module.items,
+            module.items.without_spans(),
             vec![ModuleItem::ValueDeclaration(ValueDeclaration {
+                doc: None,
+                attributes: vec![],
                 name: Id("Print".to_string()),
-                value: Expression::Function(vec![
+                value: ExpressionKind::Function(vec![
                     FunClause {
                         args: vec![],
-                        body: Expression::LiteralString("Joe".to_string())
+                        guard: None,
+                        body: ExpressionKind::LiteralString("Joe".to_string()).into(),
+                        span: Span::default()
                     },
                     FunClause {
-                        args: vec![Pattern::Bind(Id("A".to_string()))],
-                        body: Expression::LiteralString("Robert".to_string())
+                        args: vec![PatternKind::Bind(Id("A".to_string())).into()],
+                        guard: None,
+                        body: ExpressionKind::LiteralString("Robert".to_string()).into(),
+                        span: Span::default()
                     },
                     FunClause {
                         args: vec![
-                            Pattern::Bind(Id("A".to_string())),
-                            Pattern::Bind(Id("B".to_string())),
+                            PatternKind::Bind(Id("A".to_string())).into(),
+                            PatternKind::Bind(Id("B".to_string())).into(),
                         ],
-                        body: Expression::LiteralString("Mike".to_string())
+                        guard: None,
+                        body: ExpressionKind::LiteralString("Mike".to_string()).into(),
+                        span: Span::default()
                     },
                     FunClause {
                         args: vec![
-                            Pattern::Bind(Id("A".to_string())),
-                            Pattern::Bind(Id("B".to_string())),
+                            PatternKind::Bind(Id("A".to_string())).into(),
+                            PatternKind::Bind(Id("B".to_string())).into(),
                         ],
-                        body: Expression::LiteralString("Bogdan".to_string())
+                        guard: None,
+                        body: ExpressionKind::LiteralString("Bogdan".to_string()).into(),
+                        span: Span::default()
                     }
                 ])
+                .into(),
+                span: Span::default()
             })]
         );
     }
@@ -351,16 +3726,23 @@ mod tests {
         assert_eq!(module.name, Id("test_module".to_string()));
         assert_eq!(module.items.len(), 1);
         assert_eq!(
-            module.items,
+            module.items.without_spans(),
             vec![ModuleItem::ValueDeclaration(ValueDeclaration {
+                doc: None,
+                attributes: vec![],
                 name: Id("Print".to_string()),
-                value: Expression::Function(vec![FunClause {
-                    args: vec![Pattern::Bind(Id("Arg".to_string()))],
-                    body: Expression::Call {
-                        id: Id("Print".to_string()),
+                value: ExpressionKind::Function(vec![FunClause {
+                    args: vec![PatternKind::Bind(Id("Arg".to_string())).into()],
+                    guard: None,
+                    body: ExpressionKind::Call {
+                        callee: var("Print"),
                         args: vec![]
                     }
+                    .into(),
+                    span: Span::default()
                 }])
+                .into(),
+                span: Span::default()
             })]
         );
     }
@@ -380,16 +3762,23 @@ mod tests {
         assert_eq!(module.name, Id("test_module".to_string()));
         assert_eq!(module.items.len(), 1);
         assert_eq!(
-            module.items,
+            module.items.without_spans(),
             vec![ModuleItem::ValueDeclaration(ValueDeclaration {
+                doc: None,
+                attributes: vec![],
                 name: Id("Print".to_string()),
-                value: Expression::Function(vec![FunClause {
-                    args: vec![Pattern::Bind(Id("Arg".to_string()))],
-                    body: Expression::Call {
-                        id: Id("Print".to_string()),
-                        args: vec![Expression::Variable(Id("Arg".to_string()))]
+                value: ExpressionKind::Function(vec![FunClause {
+                    args: vec![PatternKind::Bind(Id("Arg".to_string())).into()],
+                    guard: None,
+                    body: ExpressionKind::Call {
+                        callee: var("Print"),
+                        args: vec![ExpressionKind::Variable(Id("Arg".to_string())).into()]
                     }
+                    .into(),
+                    span: Span::default()
                 }])
+                .into(),
+                span: Span::default()
             })]
         );
     }
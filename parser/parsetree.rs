@@ -1,34 +1,495 @@
+use crate::token::Token;
+use miette::SourceSpan;
+use std::time::Duration;
+
+/// Where a node was written in its source file. Nodes that the compiler makes
+/// up on its own get the default, empty span.
+///
+/// Spans take part in equality, so to compare two nodes by their structure
+/// alone, compare them [without their spans](StripSpans::without_spans).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Span(pub SourceSpan);
+
+impl Span {
+    pub fn new(offset: usize, len: usize) -> Self {
+        Self((offset, len).into())
+    }
+
+    pub fn offset(&self) -> usize {
+        self.0.offset()
+    }
+
+    pub fn end(&self) -> usize {
+        self.0.offset() + self.0.len()
+    }
+
+    /// The smallest span covering both `self` and `other`.
+    pub fn to(&self, other: Span) -> Span {
+        let offset = self.offset().min(other.offset());
+        Span::new(offset, self.end().max(other.end()) - offset)
+    }
+}
+
+impl Default for Span {
+    fn default() -> Self {
+        Span::new(0, 0)
+    }
+}
+
+impl From<SourceSpan> for Span {
+    fn from(span: SourceSpan) -> Self {
+        Self(span)
+    }
+}
+
+impl From<Span> for SourceSpan {
+    fn from(span: Span) -> Self {
+        span.0
+    }
+}
+
 #[derive(Clone, Debug, Hash, Eq, PartialEq)]
 pub struct Id(pub String);
 
+/// A name that lives in another module, like `nat:next` or `std.io.print`.
+/// The parser keeps the path as written, and name resolution replaces it with
+/// the full path from the entry module.
+#[derive(Clone, Debug, Hash, Eq, PartialEq)]
+pub struct RemoteId {
+    pub path: Vec<Id>,
+    pub name: Id,
+}
+
+impl std::fmt::Display for RemoteId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for module in &self.path {
+            write!(f, "{}:", module.0)?;
+        }
+        write!(f, "{}", self.name.0)
+    }
+}
+
+/// A constant that a pattern can match against.
 #[derive(Clone, Debug, PartialEq)]
-pub enum Pattern {
+pub enum Literal {
+    Bool(bool),
+    Atom(String),
+    Integer(i64),
+    Float(f64),
+    Duration(Duration),
+    String(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PatternKind {
+    /// `_`, which matches anything and binds nothing.
+    Wildcard,
     Bind(Id),
+    Literal(Literal),
+    /// `(a, b, c)`. The empty tuple `()` matches unit.
+    Tuple(Vec<Pattern>),
+    /// `[]`, the empty list.
+    Nil,
+    /// `[head | tail]`. A pattern like `[a, b]` is parsed as
+    /// `[a | [b | []]]`.
+    Cons {
+        head: Box<Pattern>,
+        tail: Box<Pattern>,
+    },
+    /// `User { name: n, age }`, or `geo:Point { x }` for a struct of
+    /// another module. Fields that aren't listed aren't matched.
+    Struct {
+        /// The module that declares the struct, which is empty until name
+        /// resolution when it's the module the pattern is written in.
+        module: Vec<Id>,
+        name: Id,
+        fields: Vec<(Id, Pattern)>,
+    },
+    /// `Succ(n)`, or `nat:Succ(n)` and `flags:On` for a variant of another
+    /// module.
+    Enum {
+        /// The module that declares the variant, like `module` in
+        /// [PatternKind::Struct].
+        module: Vec<Id>,
+        name: Id,
+        args: Vec<Pattern>,
+    },
+    /// `pattern as name` binds the whole value as well as the inner bindings.
+    As {
+        pattern: Box<Pattern>,
+        alias: Id,
+    },
 }
 
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pattern {
+    pub kind: PatternKind,
+    pub span: Span,
+}
+
+impl Pattern {
+    pub fn new(kind: PatternKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+}
+
+impl From<PatternKind> for Pattern {
+    fn from(kind: PatternKind) -> Self {
+        Self::new(kind, Span::default())
+    }
+}
+
+/// One `(args) when guard { body }` clause of a function. The clause only
+/// applies when the arguments match and the optional guard is true.
 #[derive(Clone, Debug, PartialEq)]
 pub struct FunClause {
     pub args: Vec<Pattern>,
+    pub guard: Option<Expression>,
+    pub body: Expression,
+    pub span: Span,
+}
+
+/// One `pattern when guard => body` arm of a `match` or `receive`
+/// expression.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub guard: Option<Expression>,
+    pub body: Expression,
+    pub span: Span,
+}
+
+/// The `after 1000ms { body }` clause of a `receive`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReceiveTimeout {
+    pub timeout: Expression,
     pub body: Expression,
+    pub span: Span,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Equal,
+    NotEqual,
+    LessThan,
+    GreaterThan,
+    LessEqual,
+    GreaterEqual,
+    And,
+    Or,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum StringPart {
+    Literal(String),
+    Expression(Expression),
 }
 
 #[derive(Clone, Debug, PartialEq)]
-pub enum Expression {
+pub enum ExpressionKind {
     Variable(Id),
+    /// `nat:zero`
+    Remote(RemoteId),
+    Unit,
+    Tuple(Vec<Expression>),
+    List(Vec<Expression>),
+    Bool(bool),
+    Atom(String),
+    Integer(i64),
+    Float(f64),
+    Duration(Duration),
     LiteralString(String),
-    Call { id: Id, args: Vec<Expression> },
+    InterpolatedString(Vec<StringPart>),
+    /// `f(x)`, `nat:next(n)` or `make_adder(1)(2)`. The callee can be any
+    /// expression that evaluates to a function. `std.io.print(s)` is parsed
+    /// as a method call, and name resolution turns it into a call of the
+    /// remote `std:io:print`.
+    Call {
+        callee: Box<Expression>,
+        args: Vec<Expression>,
+    },
     Function(Vec<FunClause>),
+    /// `User { name: "joe", age }`. A field without a value takes the value
+    /// of the variable with the same name. This is also the value a struct
+    /// literal evaluates to, with its fields in declaration order and the
+    /// path of the `module` that declares the struct, which the parser leaves
+    /// empty.
+    Struct {
+        name: Id,
+        fields: Vec<(Id, Expression)>,
+        module: Vec<Id>,
+    },
+    /// An enum value like `Succ(Zero)`, and the path of the module that
+    /// declares its enum. The parser reads constructors as calls and
+    /// variables, so only the interpreter builds these.
+    Variant {
+        name: Id,
+        args: Vec<Expression>,
+        module: Vec<Id>,
+    },
+    /// A function value: the clauses of a function literal, the local
+    /// bindings in scope where it was evaluated, and the path of the module
+    /// it was written in. Only the interpreter builds these.
+    Closure {
+        clauses: Vec<FunClause>,
+        captured: Vec<(Id, Expression)>,
+        module: Vec<Id>,
+    },
+    /// `user.name`
+    FieldAccess {
+        expr: Box<Expression>,
+        field: Id,
+    },
+    /// `value.method(args)`, which calls `method(value, args)`. The function
+    /// comes from the module that declares the type of `value` if it has one
+    /// by that name, and from the scope of the call otherwise.
+    MethodCall {
+        receiver: Box<Expression>,
+        method: Id,
+        args: Vec<Expression>,
+    },
+    /// `{ user | name: "b" }`, a copy of a struct with some fields replaced.
+    StructUpdate {
+        expr: Box<Expression>,
+        fields: Vec<(Id, Expression)>,
+    },
+    /// `{ a; b; c }`, a sequence of expressions that evaluates to its last
+    /// one. Bindings made inside a block don't outlive it.
+    Block(Vec<Expression>),
+    /// `name = value` inside a block. Binding a name again shadows the
+    /// previous value until the end of the block. Names bound with
+    /// `mut name = value` can also be assigned to.
+    Let {
+        name: Id,
+        value: Box<Expression>,
+        mutable: bool,
+    },
+    /// `name <- value`, which updates a `mut` binding in place.
+    Assign {
+        name: Id,
+        value: Box<Expression>,
+    },
+    Match {
+        expr: Box<Expression>,
+        arms: Vec<MatchArm>,
+    },
+    /// `spawn { body }`, which runs `body` in a new process.
+    Spawn(Box<Expression>),
+    /// `receive { pattern => body } after 1000ms { body }`, which waits for a
+    /// message that matches one of the arms, or until the timeout runs out.
+    Receive {
+        arms: Vec<MatchArm>,
+        after: Option<Box<ReceiveTimeout>>,
+    },
+    /// `process.send(message)`. The parser reads this as a method call, and
+    /// name resolution turns it into a send unless `process` is a module or
+    /// `send` is a value in scope.
+    Send {
+        process: Box<Expression>,
+        message: Box<Expression>,
+    },
+    BinaryOp {
+        op: BinaryOp,
+        lhs: Box<Expression>,
+        rhs: Box<Expression>,
+    },
+    UnaryOp {
+        op: UnaryOp,
+        expr: Box<Expression>,
+    },
+}
+
+/// An expression, and in the interpreter also the value it evaluates to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Expression {
+    pub kind: ExpressionKind,
+    pub span: Span,
+}
+
+impl Expression {
+    pub fn new(kind: ExpressionKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+
+    /// `a.b.c` as the path `a`, `b`, `c`, if the expression is nothing but
+    /// names joined by dots. Whether that is a module path or field accesses
+    /// is up to name resolution.
+    pub fn as_path(&self) -> Option<Vec<Id>> {
+        match &self.kind {
+            ExpressionKind::Variable(id) => Some(vec![id.clone()]),
+            ExpressionKind::FieldAccess { expr, field } => {
+                let mut path = expr.as_path()?;
+                path.push(field.clone());
+                Some(path)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl From<ExpressionKind> for Expression {
+    fn from(kind: ExpressionKind) -> Self {
+        Self::new(kind, Span::default())
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Delimiter {
+    Parens,
+    Brackets,
+    Braces,
+}
+
+/// The arguments of an attribute are kept as unparsed tokens, grouped by the
+/// brackets they're in, so that each macro can read them however it wants.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TokenTree {
+    Token {
+        token: Token,
+        span: Span,
+    },
+    Group {
+        delimiter: Delimiter,
+        trees: Vec<TokenTree>,
+        span: Span,
+    },
+}
+
+/// `@derive(Debug, Serializer)` or `@cli`, right before a declaration.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Attribute {
+    /// The segments of a path like `@std.derive`.
+    pub path: Vec<Id>,
+    pub args: Vec<TokenTree>,
+    pub span: Span,
+}
+
+impl Attribute {
+    /// The path as written, like `std.derive`.
+    pub fn name(&self) -> String {
+        let segments: Vec<&str> = self.path.iter().map(|id| id.0.as_str()).collect();
+        segments.join(".")
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct ValueDeclaration {
+    /// The contents of the `///` comments right before the declaration, one
+    /// line per comment.
+    pub doc: Option<String>,
+    pub attributes: Vec<Attribute>,
     pub name: Id,
     pub value: Expression,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TypeKind {
+    /// `String`, `Nat`
+    Name(Id),
+    /// `(Int, String)`. The empty tuple `()` is the unit type.
+    Tuple(Vec<Type>),
+    /// `[Int]`
+    List(Box<Type>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Type {
+    pub kind: TypeKind,
+    pub span: Span,
+}
+
+impl Type {
+    pub fn new(kind: TypeKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+}
+
+impl From<TypeKind> for Type {
+    fn from(kind: TypeKind) -> Self {
+        Self::new(kind, Span::default())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldDeclaration {
+    /// The contents of the `///` comments right before the field.
+    pub doc: Option<String>,
+    pub attributes: Vec<Attribute>,
+    pub name: Id,
+    pub ty: Type,
+    pub span: Span,
+}
+
+/// `struct User { name: String }`
+#[derive(Clone, Debug, PartialEq)]
+pub struct StructDeclaration {
+    pub doc: Option<String>,
+    pub attributes: Vec<Attribute>,
+    pub name: Id,
+    pub fields: Vec<FieldDeclaration>,
+    pub span: Span,
+}
+
+/// One variant of an enum, like `Zero` or `Succ(Nat)`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VariantDeclaration {
+    /// The contents of the `///` comments right before the variant.
+    pub doc: Option<String>,
+    pub attributes: Vec<Attribute>,
+    pub name: Id,
+    pub args: Vec<Type>,
+    pub span: Span,
+}
+
+/// `enum Nat { Zero, Succ(Nat) }`
+#[derive(Clone, Debug, PartialEq)]
+pub struct EnumDeclaration {
+    pub doc: Option<String>,
+    pub attributes: Vec<Attribute>,
+    pub name: Id,
+    pub variants: Vec<VariantDeclaration>,
+    pub span: Span,
+}
+
+/// `mod nat`, which makes `nat.q` in the module's folder a submodule.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModDeclaration {
+    pub doc: Option<String>,
+    pub attributes: Vec<Attribute>,
+    pub name: Id,
+    pub span: Span,
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum ModuleItem {
     ValueDeclaration(ValueDeclaration),
+    StructDeclaration(StructDeclaration),
+    EnumDeclaration(EnumDeclaration),
+    ModDeclaration(ModDeclaration),
+}
+
+impl ModuleItem {
+    /// The name the item declares.
+    pub fn name(&self) -> &Id {
+        match self {
+            ModuleItem::ValueDeclaration(vd) => &vd.name,
+            ModuleItem::StructDeclaration(decl) => &decl.name,
+            ModuleItem::EnumDeclaration(decl) => &decl.name,
+            ModuleItem::ModDeclaration(decl) => &decl.name,
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -36,3 +497,254 @@ pub struct Module {
     pub name: Id,
     pub items: Vec<ModuleItem>,
 }
+
+/// Resets the spans of a node and everything in it to the default, empty
+/// span, so that nodes can be compared no matter where they were written.
+pub trait StripSpans: Sized {
+    fn strip_spans(&mut self);
+
+    fn without_spans(mut self) -> Self {
+        self.strip_spans();
+        self
+    }
+}
+
+impl<T: StripSpans> StripSpans for Vec<T> {
+    fn strip_spans(&mut self) {
+        for node in self {
+            node.strip_spans();
+        }
+    }
+}
+
+impl<T: StripSpans> StripSpans for Option<T> {
+    fn strip_spans(&mut self) {
+        if let Some(node) = self {
+            node.strip_spans();
+        }
+    }
+}
+
+impl<T: StripSpans> StripSpans for Box<T> {
+    fn strip_spans(&mut self) {
+        self.as_mut().strip_spans();
+    }
+}
+
+impl<T: StripSpans> StripSpans for (Id, T) {
+    fn strip_spans(&mut self) {
+        self.1.strip_spans();
+    }
+}
+
+impl StripSpans for Pattern {
+    fn strip_spans(&mut self) {
+        self.span = Span::default();
+        match &mut self.kind {
+            PatternKind::Tuple(patterns) => patterns.strip_spans(),
+            PatternKind::Cons { head, tail } => {
+                head.strip_spans();
+                tail.strip_spans();
+            }
+            PatternKind::Struct { fields, .. } => fields.strip_spans(),
+            PatternKind::Enum { args, .. } => args.strip_spans(),
+            PatternKind::As { pattern, .. } => pattern.strip_spans(),
+            PatternKind::Wildcard
+            | PatternKind::Bind(_)
+            | PatternKind::Literal(_)
+            | PatternKind::Nil => {}
+        }
+    }
+}
+
+impl StripSpans for FunClause {
+    fn strip_spans(&mut self) {
+        self.span = Span::default();
+        self.args.strip_spans();
+        self.guard.strip_spans();
+        self.body.strip_spans();
+    }
+}
+
+impl StripSpans for MatchArm {
+    fn strip_spans(&mut self) {
+        self.span = Span::default();
+        self.pattern.strip_spans();
+        self.guard.strip_spans();
+        self.body.strip_spans();
+    }
+}
+
+impl StripSpans for ReceiveTimeout {
+    fn strip_spans(&mut self) {
+        self.span = Span::default();
+        self.timeout.strip_spans();
+        self.body.strip_spans();
+    }
+}
+
+impl StripSpans for StringPart {
+    fn strip_spans(&mut self) {
+        if let StringPart::Expression(expr) = self {
+            expr.strip_spans();
+        }
+    }
+}
+
+impl StripSpans for Expression {
+    fn strip_spans(&mut self) {
+        self.span = Span::default();
+        self.kind.strip_spans();
+    }
+}
+
+impl StripSpans for ExpressionKind {
+    fn strip_spans(&mut self) {
+        match self {
+            ExpressionKind::Tuple(exprs)
+            | ExpressionKind::List(exprs)
+            | ExpressionKind::Block(exprs)
+            | ExpressionKind::Variant { args: exprs, .. } => exprs.strip_spans(),
+            ExpressionKind::InterpolatedString(parts) => parts.strip_spans(),
+            ExpressionKind::Call { callee, args } => {
+                callee.strip_spans();
+                args.strip_spans();
+            }
+            ExpressionKind::Function(clauses) => clauses.strip_spans(),
+            ExpressionKind::Closure {
+                clauses, captured, ..
+            } => {
+                clauses.strip_spans();
+                captured.strip_spans();
+            }
+            ExpressionKind::Struct { fields, .. } => fields.strip_spans(),
+            ExpressionKind::FieldAccess { expr, .. } => expr.strip_spans(),
+            ExpressionKind::MethodCall { receiver, args, .. } => {
+                receiver.strip_spans();
+                args.strip_spans();
+            }
+            ExpressionKind::StructUpdate { expr, fields } => {
+                expr.strip_spans();
+                fields.strip_spans();
+            }
+            ExpressionKind::Let { value, .. } | ExpressionKind::Assign { value, .. } => {
+                value.strip_spans()
+            }
+            ExpressionKind::Match { expr, arms } => {
+                expr.strip_spans();
+                arms.strip_spans();
+            }
+            ExpressionKind::Spawn(body) => body.strip_spans(),
+            ExpressionKind::Receive { arms, after } => {
+                arms.strip_spans();
+                after.strip_spans();
+            }
+            ExpressionKind::Send { process, message } => {
+                process.strip_spans();
+                message.strip_spans();
+            }
+            ExpressionKind::BinaryOp { lhs, rhs, .. } => {
+                lhs.strip_spans();
+                rhs.strip_spans();
+            }
+            ExpressionKind::UnaryOp { expr, .. } => expr.strip_spans(),
+            ExpressionKind::Variable(_)
+            | ExpressionKind::Remote(_)
+            | ExpressionKind::Unit
+            | ExpressionKind::Bool(_)
+            | ExpressionKind::Atom(_)
+            | ExpressionKind::Integer(_)
+            | ExpressionKind::Float(_)
+            | ExpressionKind::Duration(_)
+            | ExpressionKind::LiteralString(_) => {}
+        }
+    }
+}
+
+impl StripSpans for TokenTree {
+    fn strip_spans(&mut self) {
+        match self {
+            TokenTree::Token { span, .. } => *span = Span::default(),
+            TokenTree::Group { trees, span, .. } => {
+                *span = Span::default();
+                trees.strip_spans();
+            }
+        }
+    }
+}
+
+impl StripSpans for Attribute {
+    fn strip_spans(&mut self) {
+        self.span = Span::default();
+        self.args.strip_spans();
+    }
+}
+
+impl StripSpans for Type {
+    fn strip_spans(&mut self) {
+        self.span = Span::default();
+        match &mut self.kind {
+            TypeKind::Name(_) => {}
+            TypeKind::Tuple(types) => types.strip_spans(),
+            TypeKind::List(ty) => ty.strip_spans(),
+        }
+    }
+}
+
+impl StripSpans for ValueDeclaration {
+    fn strip_spans(&mut self) {
+        self.span = Span::default();
+        self.attributes.strip_spans();
+        self.value.strip_spans();
+    }
+}
+
+impl StripSpans for FieldDeclaration {
+    fn strip_spans(&mut self) {
+        self.span = Span::default();
+        self.attributes.strip_spans();
+        self.ty.strip_spans();
+    }
+}
+
+impl StripSpans for StructDeclaration {
+    fn strip_spans(&mut self) {
+        self.span = Span::default();
+        self.attributes.strip_spans();
+        self.fields.strip_spans();
+    }
+}
+
+impl StripSpans for VariantDeclaration {
+    fn strip_spans(&mut self) {
+        self.span = Span::default();
+        self.attributes.strip_spans();
+        self.args.strip_spans();
+    }
+}
+
+impl StripSpans for EnumDeclaration {
+    fn strip_spans(&mut self) {
+        self.span = Span::default();
+        self.attributes.strip_spans();
+        self.variants.strip_spans();
+    }
+}
+
+impl StripSpans for ModDeclaration {
+    fn strip_spans(&mut self) {
+        self.span = Span::default();
+        self.attributes.strip_spans();
+    }
+}
+
+impl StripSpans for ModuleItem {
+    fn strip_spans(&mut self) {
+        match self {
+            ModuleItem::ValueDeclaration(decl) => decl.strip_spans(),
+            ModuleItem::StructDeclaration(decl) => decl.strip_spans(),
+            ModuleItem::EnumDeclaration(decl) => decl.strip_spans(),
+            ModuleItem::ModDeclaration(decl) => decl.strip_spans(),
+        }
+    }
+}
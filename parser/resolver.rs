@@ -0,0 +1,910 @@
+use crate::loader::{ModuleGraph, ModuleId};
+use crate::parsetree::*;
+use miette::{Diagnostic, NamedSource, SourceSpan};
+use q_core::suggest::did_you_mean;
+use std::collections::HashSet;
+use std::sync::Arc;
+use thiserror::Error;
+
+#[derive(Error, Diagnostic, Debug)]
+pub enum ResolveError {
+    #[error("There is no module {name}")]
+    UnknownModule {
+        name: String,
+        #[help]
+        help: Option<String>,
+        #[source_code]
+        src: Arc<NamedSource>,
+        #[label("unknown module")]
+        span: SourceSpan,
+    },
+
+    #[error("The module {module} has no {name}")]
+    UnknownName {
+        module: String,
+        name: String,
+        #[help]
+        help: Option<String>,
+        #[source_code]
+        src: Arc<NamedSource>,
+        #[label("not declared in {module}")]
+        span: SourceSpan,
+    },
+
+    #[error("{name} is a value, not a module")]
+    NotAModule {
+        name: String,
+        #[source_code]
+        src: Arc<NamedSource>,
+        #[label("this is a local value")]
+        span: SourceSpan,
+    },
+
+    #[error("{name} can't be assigned to because it isn't declared with `mut`")]
+    #[diagnostic(help("declare it with `mut {name} = ...` to assign to it later"))]
+    AssignToImmutable {
+        name: String,
+        #[source_code]
+        src: Arc<NamedSource>,
+        #[label("assigned here")]
+        span: SourceSpan,
+    },
+
+    #[error("{name} can't be assigned to because the function only captured a copy of it")]
+    #[diagnostic(help(
+        "return the new value from the function and assign it to {name} where it is declared"
+    ))]
+    AssignToCaptured {
+        name: String,
+        #[source_code]
+        src: Arc<NamedSource>,
+        #[label("assigned here")]
+        span: SourceSpan,
+    },
+}
+
+impl q_core::diagnostic::Diagnostic for ResolveError {}
+
+/// The modules that come with the language and have no file, with the names
+/// each of them declares.
+pub const BUILTINS: &[(&[&str], &[&str])] = &[(&["std", "io"], &["print"])];
+
+/// Replaces the path of every qualified name in the graph with the full path
+/// of the module that declares it, like `main`, `math`, `nat` for `nat:next`
+/// written in `math.q`. Builtin paths like `std.io` are kept as they are.
+///
+/// A dotted name like `math.pi` is a qualified name too, unless it starts
+/// with a local value, in which case it stays a field access.
+///
+/// A method call `process.send(message)` that isn't a qualified name or a
+/// call of a `send` in scope becomes a [send](ExpressionKind::Send).
+///
+/// Assigning to a name in scope that isn't declared with `mut` is reported
+/// here, whether or not the assignment would ever run.
+pub fn resolve(graph: &mut ModuleGraph) -> Vec<ResolveError> {
+    let exports: Vec<HashSet<Id>> = graph
+        .iter()
+        .map(|(_, loaded)| exported_names(&loaded.module))
+        .collect();
+    let structs: Vec<HashSet<Id>> = graph
+        .iter()
+        .map(|(_, loaded)| struct_names(&loaded.module))
+        .collect();
+
+    let mut diagnostics = vec![];
+    for index in 0..graph.len() {
+        let id = ModuleId(index);
+        let mut items = std::mem::take(&mut graph.get_mut(id).module.items);
+
+        let mut resolver = Resolver {
+            graph: &*graph,
+            exports: &exports,
+            structs: &structs,
+            module: id,
+            locals: items
+                .iter()
+                .filter(|item| !matches!(item, ModuleItem::ModDeclaration(_)))
+                .map(|item| Local {
+                    name: item.name().clone(),
+                    mutable: false,
+                })
+                .collect(),
+            function_scope: 0,
+            src: graph.get(id).source.clone(),
+            diagnostics: vec![],
+        };
+        for item in &mut items {
+            if let ModuleItem::ValueDeclaration(vd) = item {
+                resolver.resolve_expression(&mut vd.value);
+            }
+        }
+        diagnostics.append(&mut resolver.diagnostics);
+
+        graph.get_mut(id).module.items = items;
+    }
+    diagnostics
+}
+
+/// The names other modules can refer to: values and enum variants.
+fn exported_names(module: &Module) -> HashSet<Id> {
+    let mut names = HashSet::new();
+    for item in &module.items {
+        match item {
+            ModuleItem::ValueDeclaration(vd) => {
+                names.insert(vd.name.clone());
+            }
+            ModuleItem::EnumDeclaration(decl) => {
+                names.extend(decl.variants.iter().map(|variant| variant.name.clone()));
+            }
+            ModuleItem::StructDeclaration(_) | ModuleItem::ModDeclaration(_) => {}
+        }
+    }
+    names
+}
+
+/// The structs a module declares.
+fn struct_names(module: &Module) -> HashSet<Id> {
+    module
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            ModuleItem::StructDeclaration(decl) => Some(decl.name.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// A name in scope that isn't a module.
+struct Local {
+    name: Id,
+    mutable: bool,
+}
+
+/// The names a qualified name can refer to, since values and structs are
+/// looked up among different names.
+#[derive(Copy, Clone)]
+enum Namespace {
+    Values,
+    Structs,
+}
+
+struct Resolver<'a> {
+    graph: &'a ModuleGraph,
+    exports: &'a [HashSet<Id>],
+    /// The structs every module declares, which patterns can refer to.
+    structs: &'a [HashSet<Id>],
+    module: ModuleId,
+    /// The names in scope that aren't modules, innermost last.
+    locals: Vec<Local>,
+    /// Where the locals of the innermost function start. The ones below it
+    /// were captured when the function was created.
+    function_scope: usize,
+    src: Arc<NamedSource>,
+    diagnostics: Vec<ResolveError>,
+}
+
+impl Resolver<'_> {
+    fn resolve_expression(&mut self, expr: &mut Expression) {
+        let span = expr.span;
+        match expr.kind {
+            ExpressionKind::FieldAccess { .. } => {
+                if let Some(remote) = self.remote_field_access(expr) {
+                    *expr = remote;
+                }
+            }
+            ExpressionKind::MethodCall { .. } => {
+                if let Some(remote) = self.remote_method_call(expr) {
+                    expr.kind = remote;
+                } else if let Some(send) = self.send(expr) {
+                    expr.kind = send;
+                }
+            }
+            _ => {}
+        }
+
+        match &mut expr.kind {
+            ExpressionKind::Remote(id) => self.resolve_remote(id, span),
+            ExpressionKind::Call { callee, args } => {
+                self.resolve_expression(callee);
+                self.resolve_all(args);
+            }
+            ExpressionKind::Tuple(exprs)
+            | ExpressionKind::List(exprs)
+            | ExpressionKind::Variant { args: exprs, .. } => self.resolve_all(exprs),
+            ExpressionKind::InterpolatedString(parts) => {
+                for part in parts {
+                    if let StringPart::Expression(expr) = part {
+                        self.resolve_expression(expr);
+                    }
+                }
+            }
+            ExpressionKind::Function(clauses) => {
+                let function_scope = self.function_scope;
+                for clause in clauses {
+                    let scope = self.locals.len();
+                    self.function_scope = scope;
+                    for arg in &mut clause.args {
+                        self.resolve_pattern(arg);
+                    }
+                    if let Some(guard) = &mut clause.guard {
+                        self.resolve_expression(guard);
+                    }
+                    self.resolve_expression(&mut clause.body);
+                    self.locals.truncate(scope);
+                }
+                self.function_scope = function_scope;
+            }
+            ExpressionKind::Struct { fields, .. } => {
+                for (_, value) in fields {
+                    self.resolve_expression(value);
+                }
+            }
+            ExpressionKind::StructUpdate { expr, fields } => {
+                self.resolve_expression(expr);
+                for (_, value) in fields {
+                    self.resolve_expression(value);
+                }
+            }
+            ExpressionKind::FieldAccess { expr, .. } => self.resolve_expression(expr),
+            ExpressionKind::MethodCall { receiver, args, .. } => {
+                self.resolve_expression(receiver);
+                self.resolve_all(args);
+            }
+            ExpressionKind::Block(statements) => {
+                let scope = self.locals.len();
+                self.resolve_all(statements);
+                self.locals.truncate(scope);
+            }
+            ExpressionKind::Let {
+                name,
+                value,
+                mutable,
+            } => {
+                self.resolve_expression(value);
+                self.bind(name, *mutable);
+            }
+            ExpressionKind::Assign { name, value } => {
+                self.resolve_expression(value);
+                self.check_assignment(name, span);
+            }
+            ExpressionKind::Match { expr, arms } => {
+                self.resolve_expression(expr);
+                self.resolve_arms(arms);
+            }
+            ExpressionKind::Spawn(body) => self.resolve_expression(body),
+            ExpressionKind::Receive { arms, after } => {
+                self.resolve_arms(arms);
+                if let Some(after) = after {
+                    self.resolve_expression(&mut after.timeout);
+                    self.resolve_expression(&mut after.body);
+                }
+            }
+            ExpressionKind::Send { process, message } => {
+                self.resolve_expression(process);
+                self.resolve_expression(message);
+            }
+            ExpressionKind::BinaryOp { lhs, rhs, .. } => {
+                self.resolve_expression(lhs);
+                self.resolve_expression(rhs);
+            }
+            ExpressionKind::UnaryOp { expr, .. } => self.resolve_expression(expr),
+            // closures only exist at run time, long after names are resolved
+            ExpressionKind::Closure { .. }
+            | ExpressionKind::Variable(_)
+            | ExpressionKind::Unit
+            | ExpressionKind::Bool(_)
+            | ExpressionKind::Atom(_)
+            | ExpressionKind::Integer(_)
+            | ExpressionKind::Float(_)
+            | ExpressionKind::Duration(_)
+            | ExpressionKind::LiteralString(_) => {}
+        }
+    }
+
+    fn resolve_all(&mut self, exprs: &mut [Expression]) {
+        for expr in exprs {
+            self.resolve_expression(expr);
+        }
+    }
+
+    fn resolve_arms(&mut self, arms: &mut [MatchArm]) {
+        for arm in arms {
+            let scope = self.locals.len();
+            self.resolve_pattern(&mut arm.pattern);
+            if let Some(guard) = &mut arm.guard {
+                self.resolve_expression(guard);
+            }
+            self.resolve_expression(&mut arm.body);
+            self.locals.truncate(scope);
+        }
+    }
+
+    fn bind(&mut self, name: &Id, mutable: bool) {
+        self.locals.push(Local {
+            name: name.clone(),
+            mutable,
+        });
+    }
+
+    fn is_local(&self, name: &Id) -> bool {
+        self.locals.iter().any(|local| local.name == *name)
+    }
+
+    /// Reports an assignment to a local that wasn't declared with `mut`, or
+    /// that a function captured from outside of it. Names that aren't in
+    /// scope at all are left for the interpreter.
+    fn check_assignment(&mut self, name: &Id, span: Span) {
+        let Some(index) = self.locals.iter().rposition(|local| local.name == *name) else {
+            return;
+        };
+        let name = name.0.clone();
+        let src = self.src.clone();
+        let span = span.into();
+        if !self.locals[index].mutable {
+            self.diagnostics
+                .push(ResolveError::AssignToImmutable { name, src, span });
+        } else if index < self.function_scope {
+            self.diagnostics
+                .push(ResolveError::AssignToCaptured { name, src, span });
+        }
+    }
+
+    /// Brings the names the pattern binds into scope, and replaces the module
+    /// of every qualified variant or struct in it with its full path.
+    fn resolve_pattern(&mut self, pattern: &mut Pattern) {
+        let span = pattern.span;
+        match &mut pattern.kind {
+            PatternKind::Bind(id) => self.bind(id, false),
+            PatternKind::Tuple(patterns) => {
+                for pattern in patterns {
+                    self.resolve_pattern(pattern);
+                }
+            }
+            PatternKind::Enum { module, name, args } => {
+                self.resolve_pattern_module(module, name, Namespace::Values, span);
+                for pattern in args {
+                    self.resolve_pattern(pattern);
+                }
+            }
+            PatternKind::Cons { head, tail } => {
+                self.resolve_pattern(head);
+                self.resolve_pattern(tail);
+            }
+            PatternKind::Struct {
+                module,
+                name,
+                fields,
+            } => {
+                self.resolve_pattern_module(module, name, Namespace::Structs, span);
+                for (_, pattern) in fields {
+                    self.resolve_pattern(pattern);
+                }
+            }
+            PatternKind::As { pattern, alias } => {
+                self.resolve_pattern(pattern);
+                self.bind(alias, false);
+            }
+            PatternKind::Wildcard | PatternKind::Literal(_) | PatternKind::Nil => {}
+        }
+    }
+
+    /// Qualifies the module of a variant or struct in a pattern, unless it's
+    /// the module the pattern is written in.
+    fn resolve_pattern_module(
+        &mut self,
+        module: &mut Vec<Id>,
+        name: &Id,
+        namespace: Namespace,
+        span: Span,
+    ) {
+        if module.is_empty() {
+            return;
+        }
+        let id = RemoteId {
+            path: std::mem::take(module),
+            name: name.clone(),
+        };
+        match self.lookup(&id, namespace, span) {
+            Ok(path) => *module = path,
+            Err(error) => {
+                *module = id.path;
+                self.diagnostics.push(error);
+            }
+        }
+    }
+
+    /// `math.nat.zero` as the remote `math:nat:zero`, if `math` is a module
+    /// rather than a local value. Any names after the first one that isn't a
+    /// module stay field accesses on the remote value.
+    fn remote_field_access(&self, expr: &Expression) -> Option<Expression> {
+        let path = expr.as_path()?;
+        if self.is_local(&path[0]) {
+            return None;
+        }
+
+        let modules = self.module_prefix_len(&path).min(path.len() - 1);
+        if modules == 0 {
+            return None;
+        }
+
+        let id = RemoteId {
+            path: path[..modules].to_vec(),
+            name: path[modules].clone(),
+        };
+        let mut remote = Expression::new(ExpressionKind::Remote(id), expr.span);
+        for field in &path[modules + 1..] {
+            let kind = ExpressionKind::FieldAccess {
+                expr: Box::new(remote),
+                field: field.clone(),
+            };
+            remote = Expression::new(kind, expr.span);
+        }
+        Some(remote)
+    }
+
+    /// `std.io.print(s)` as a call of the remote `std:io:print`, if the whole
+    /// receiver is a path of modules rather than a value.
+    fn remote_method_call(&self, expr: &Expression) -> Option<ExpressionKind> {
+        let ExpressionKind::MethodCall {
+            receiver,
+            method,
+            args,
+        } = &expr.kind
+        else {
+            return None;
+        };
+        let path = receiver.as_path()?;
+        if self.is_local(&path[0]) || self.module_prefix_len(&path) < path.len() {
+            return None;
+        }
+
+        let id = RemoteId {
+            path,
+            name: method.clone(),
+        };
+        Some(ExpressionKind::Call {
+            callee: Box::new(Expression::new(ExpressionKind::Remote(id), expr.span)),
+            args: args.clone(),
+        })
+    }
+
+    /// The send that a method call `process.send(message)` stands for, unless
+    /// there's a function `send` in scope for it to call.
+    fn send(&self, expr: &Expression) -> Option<ExpressionKind> {
+        let ExpressionKind::MethodCall {
+            receiver,
+            method,
+            args,
+        } = &expr.kind
+        else {
+            return None;
+        };
+        let [message] = args.as_slice() else {
+            return None;
+        };
+        if method.0 != "send" || self.is_local(method) {
+            return None;
+        }
+
+        Some(ExpressionKind::Send {
+            process: receiver.clone(),
+            message: Box::new(message.clone()),
+        })
+    }
+
+    /// How many names at the start of `path` are modules.
+    fn module_prefix_len(&self, path: &[Id]) -> usize {
+        if path[0].0 == "std" {
+            return BUILTINS
+                .iter()
+                .map(|(modules, _)| {
+                    modules
+                        .iter()
+                        .zip(path)
+                        .take_while(|(module, id)| **module == id.0)
+                        .count()
+                })
+                .max()
+                .unwrap_or(0);
+        }
+
+        let mut module = self.module;
+        let mut len = 0;
+        for id in path {
+            let Some(submodule) = self.graph.submodule(module, id) else {
+                break;
+            };
+            module = submodule;
+            len += 1;
+        }
+        len
+    }
+
+    fn resolve_remote(&mut self, id: &mut RemoteId, span: Span) {
+        match self.lookup(id, Namespace::Values, span) {
+            Ok(path) => id.path = path,
+            Err(error) => self.diagnostics.push(error),
+        }
+    }
+
+    /// The full path of the module that declares `id` in `namespace`.
+    fn lookup(
+        &self,
+        id: &RemoteId,
+        namespace: Namespace,
+        span: Span,
+    ) -> Result<Vec<Id>, ResolveError> {
+        let root = &id.path[0];
+        if self.is_local(root) && self.graph.submodule(self.module, root).is_none() {
+            return Err(ResolveError::NotAModule {
+                name: root.0.clone(),
+                src: self.src.clone(),
+                span: span.into(),
+            });
+        }
+
+        if root.0 == "std" {
+            return self.lookup_builtin(id, namespace, span);
+        }
+
+        let mut module = self.module;
+        for name in &id.path {
+            module = match self.graph.submodule(module, name) {
+                Some(submodule) => submodule,
+                None => {
+                    let submodules = self.graph.get(module).submodules.keys();
+                    let mut candidates: Vec<&str> = submodules.map(|id| id.0.as_str()).collect();
+                    if module == self.module {
+                        candidates.push("std");
+                    }
+                    return Err(ResolveError::UnknownModule {
+                        name: name.0.clone(),
+                        help: suggestion(&name.0, candidates),
+                        src: self.src.clone(),
+                        span: span.into(),
+                    });
+                }
+            };
+        }
+
+        let exports = match namespace {
+            Namespace::Values => &self.exports[module.0],
+            Namespace::Structs => &self.structs[module.0],
+        };
+        if !exports.contains(&id.name) {
+            return Err(ResolveError::UnknownName {
+                module: display_path(&id.path),
+                name: id.name.0.clone(),
+                help: suggestion(&id.name.0, exports.iter().map(|id| id.0.as_str())),
+                src: self.src.clone(),
+                span: span.into(),
+            });
+        }
+
+        Ok(self.graph.get(module).path.clone())
+    }
+
+    fn lookup_builtin(
+        &self,
+        id: &RemoteId,
+        namespace: Namespace,
+        span: Span,
+    ) -> Result<Vec<Id>, ResolveError> {
+        let path = display_path(&id.path);
+        let Some((_, values)) = BUILTINS
+            .iter()
+            .find(|(modules, _)| modules.join(":") == path)
+        else {
+            let builtins: Vec<String> = BUILTINS
+                .iter()
+                .map(|(modules, _)| modules.join(":"))
+                .collect();
+            return Err(ResolveError::UnknownModule {
+                help: suggestion(&path, builtins.iter().map(String::as_str)),
+                name: path,
+                src: self.src.clone(),
+                span: span.into(),
+            });
+        };
+
+        // builtin modules don't declare any structs
+        let names: &[&str] = match namespace {
+            Namespace::Values => values,
+            Namespace::Structs => &[],
+        };
+        if !names.contains(&id.name.0.as_str()) {
+            return Err(ResolveError::UnknownName {
+                module: path,
+                name: id.name.0.clone(),
+                help: suggestion(&id.name.0, names.iter().copied()),
+                src: self.src.clone(),
+                span: span.into(),
+            });
+        }
+
+        Ok(id.path.clone())
+    }
+}
+
+fn display_path(path: &[Id]) -> String {
+    let names: Vec<&str> = path.iter().map(|id| id.0.as_str()).collect();
+    names.join(":")
+}
+
+fn suggestion<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<String> {
+    did_you_mean(name, candidates).map(|candidate| format!("did you mean `{candidate}`?"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::ModuleLoader;
+    use q_core::testing::TempProject;
+
+    /// Loads `files`, given as relative paths and contents, starting from
+    /// `main.q`.
+    fn load(files: &[(&str, &str)]) -> ModuleGraph {
+        let project = TempProject::new(files);
+        let mut loader = ModuleLoader::default();
+        let graph = loader.load(&project.path("main.q")).unwrap();
+        assert!(loader.diagnostics().is_empty());
+        graph
+    }
+
+    fn value(graph: &ModuleGraph, id: ModuleId, name: &str) -> Expression {
+        graph
+            .get(id)
+            .module
+            .items
+            .iter()
+            .find_map(|item| match item {
+                ModuleItem::ValueDeclaration(vd) if vd.name.0 == name => {
+                    Some(vd.value.clone().without_spans())
+                }
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    fn remote(path: &[&str], name: &str) -> RemoteId {
+        RemoteId {
+            path: path.iter().map(|module| Id(module.to_string())).collect(),
+            name: Id(name.to_string()),
+        }
+    }
+
+    #[test]
+    fn resolve_qualifies_paths_from_the_entry_module() {
+        let mut graph = load(&[
+                (
+                    "main.q",
+                    "mod math\nmain = math:one\npi = math.pi\nsay = std.io.print\nlog = std.io.print(pi)",
+                ),
+                ("math.q", "mod nat\none = nat:Succ(nat:Zero)\npi = 3"),
+                ("math/nat.q", "enum Nat { Zero, Succ(Nat) }"),
+            ],
+        );
+
+        let diagnostics = resolve(&mut graph);
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+
+        let main = graph.entry();
+        let math = graph.submodule(main, &Id("math".to_string())).unwrap();
+        assert_eq!(
+            value(&graph, main, "main").kind,
+            ExpressionKind::Remote(remote(&["main", "math"], "one"))
+        );
+        assert_eq!(
+            value(&graph, main, "pi").kind,
+            ExpressionKind::Remote(remote(&["main", "math"], "pi"))
+        );
+        assert_eq!(
+            value(&graph, main, "say").kind,
+            ExpressionKind::Remote(remote(&["std", "io"], "print"))
+        );
+        assert_eq!(
+            value(&graph, main, "log").kind,
+            ExpressionKind::Call {
+                callee: Box::new(ExpressionKind::Remote(remote(&["std", "io"], "print")).into()),
+                args: vec![ExpressionKind::Variable(Id("pi".to_string())).into()]
+            }
+        );
+        assert_eq!(
+            value(&graph, math, "one").kind,
+            ExpressionKind::Call {
+                callee: Box::new(
+                    ExpressionKind::Remote(remote(&["main", "math", "nat"], "Succ")).into()
+                ),
+                args: vec![ExpressionKind::Remote(remote(&["main", "math", "nat"], "Zero")).into()]
+            }
+        );
+    }
+
+    #[test]
+    fn resolve_leaves_field_accesses_on_locals_alone() {
+        let mut graph = load(&[
+            ("main.q", "mod math\nf = (math) { math.pi }"),
+            ("math.q", "pi = 3"),
+        ]);
+
+        assert!(resolve(&mut graph).is_empty());
+        let ExpressionKind::Function(clauses) = value(&graph, graph.entry(), "f").kind else {
+            panic!("expected a function");
+        };
+        assert!(matches!(
+            clauses[0].body.kind,
+            ExpressionKind::FieldAccess { .. }
+        ));
+    }
+
+    #[test]
+    fn resolve_turns_send_method_calls_into_sends() {
+        let mut graph = load(&[
+            (
+                "main.q",
+                "mod mailer\n\
+                 a = (p, m) { p.send(m) }\n\
+                 b = (p, m) { p.send(m, m) }\n\
+                 c = (m) { mailer.send(m) }",
+            ),
+            ("mailer.q", "send = (m) { m }\nrelay = (p, m) { p.send(m) }"),
+        ]);
+
+        assert!(resolve(&mut graph).is_empty());
+        let body = |module, name| {
+            let ExpressionKind::Function(clauses) = value(&graph, module, name).kind else {
+                panic!("expected a function");
+            };
+            clauses[0].body.kind.clone()
+        };
+        let main = graph.entry();
+        let mailer = graph.submodule(main, &Id("mailer".to_string())).unwrap();
+        assert_eq!(
+            body(main, "a"),
+            ExpressionKind::Send {
+                process: Box::new(ExpressionKind::Variable(Id("p".to_string())).into()),
+                message: Box::new(ExpressionKind::Variable(Id("m".to_string())).into()),
+            }
+        );
+        assert!(matches!(body(main, "b"), ExpressionKind::MethodCall { .. }));
+        assert!(matches!(
+            body(main, "c"),
+            ExpressionKind::Call { callee, .. }
+                if callee.kind == ExpressionKind::Remote(remote(&["main", "mailer"], "send"))
+        ));
+        assert!(matches!(
+            body(mailer, "relay"),
+            ExpressionKind::MethodCall { .. }
+        ));
+    }
+
+    #[test]
+    fn resolve_suggests_close_names() {
+        let mut graph = load(&[
+            (
+                "main.q",
+                "mod math\na = mat:pi\nb = math:pie\nc = std.io.prnt(1)",
+            ),
+            ("math.q", "pi = 3"),
+        ]);
+
+        let diagnostics = resolve(&mut graph);
+        let helps: Vec<Option<String>> = diagnostics
+            .iter()
+            .map(|diagnostic| match diagnostic {
+                ResolveError::UnknownModule { help, .. }
+                | ResolveError::UnknownName { help, .. } => help.clone(),
+                ResolveError::NotAModule { .. }
+                | ResolveError::AssignToImmutable { .. }
+                | ResolveError::AssignToCaptured { .. } => None,
+            })
+            .collect();
+        assert_eq!(
+            helps,
+            vec![
+                Some("did you mean `math`?".to_string()),
+                Some("did you mean `pi`?".to_string()),
+                Some("did you mean `print`?".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn resolve_rejects_assignments_to_immutable_names() {
+        let source = "limit = 3
+                      f = (n) {
+                      mut total = 0
+                      total <- total + n
+                      match n { 0 => { n <- 1 }, m => { m <- 1; limit <- 4 } }
+                      }";
+        let mut graph = load(&[("main.q", source)]);
+
+        let assigned: Vec<(String, &str)> = resolve(&mut graph)
+            .into_iter()
+            .map(|diagnostic| match diagnostic {
+                ResolveError::AssignToImmutable { name, span, .. } => {
+                    (name, &source[span.offset()..span.offset() + span.len()])
+                }
+                diagnostic => panic!("unexpected {diagnostic:?}"),
+            })
+            .collect();
+        assert_eq!(
+            assigned,
+            vec![
+                ("n".to_string(), "n <- 1"),
+                ("m".to_string(), "m <- 1"),
+                ("limit".to_string(), "limit <- 4"),
+            ]
+        );
+    }
+
+    #[test]
+    fn resolve_rejects_assignments_to_captured_names() {
+        let source = "main = (arg) {
+                      mut n = 0
+                      bump = () { n <- n + 1 }
+                      reset = () { mut n = 0; n <- 1 }
+                      n <- 2
+                      }";
+        let mut graph = load(&[("main.q", source)]);
+
+        let assigned: Vec<(String, &str)> = resolve(&mut graph)
+            .into_iter()
+            .map(|diagnostic| match diagnostic {
+                ResolveError::AssignToCaptured { name, span, .. } => {
+                    (name, &source[span.offset()..span.offset() + span.len()])
+                }
+                diagnostic => panic!("unexpected {diagnostic:?}"),
+            })
+            .collect();
+        assert_eq!(assigned, vec![("n".to_string(), "n <- n + 1")]);
+    }
+
+    #[test]
+    fn resolve_qualifies_patterns() {
+        let mut graph = load(&[
+            (
+                "main.q",
+                "mod flags
+                 f = (flags:On) { 1 }
+                 g = (x) { match x { flags:Point { x } => x, flags:Of => 0, flags:Flag => 0 } }",
+            ),
+            (
+                "flags.q",
+                "enum Flag { On, Off }
+struct Point { x: Int }",
+            ),
+        ]);
+
+        let names: Vec<String> = resolve(&mut graph)
+            .into_iter()
+            .map(|diagnostic| match diagnostic {
+                ResolveError::UnknownName { name, .. } => name,
+                diagnostic => panic!("unexpected {diagnostic:?}"),
+            })
+            .collect();
+        assert_eq!(names, vec!["Of".to_string(), "Flag".to_string()]);
+
+        let ExpressionKind::Function(clauses) = value(&graph, graph.entry(), "f").kind else {
+            panic!("expected a function");
+        };
+        assert_eq!(
+            clauses[0].args[0].kind,
+            PatternKind::Enum {
+                module: vec![Id("main".to_string()), Id("flags".to_string())],
+                name: Id("On".to_string()),
+                args: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn resolve_rejects_paths_through_locals() {
+        let mut graph = load(&[("main.q", "f = (user) { user:name }")]);
+
+        assert!(matches!(
+            resolve(&mut graph)[..],
+            [ResolveError::NotAModule { ref name, .. }] if name == "user"
+        ));
+    }
+}
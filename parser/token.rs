@@ -1,14 +1,73 @@
 use logos::Logos;
+use std::time::Duration;
 
 #[derive(Logos, Clone, Debug, PartialEq)]
 pub enum Token {
-    #[regex(r"[_a-zA-Z]+", |lex| lex.slice().parse())]
+    /// Identifiers follow Unicode's XID rules, and may end in a `?` or `!`, as
+    /// in `valid?` or `save!`. A `!` followed by `=` is left for the `!=`
+    /// operator, so `a!=b` compares `a` and `b`.
+    #[regex(r"[_\p{XID_Start}]\p{XID_Continue}*\??", identifier)]
     Id(String),
 
-    /// TODO(@ostera): figure out how to get backticks to work here :)
+    /// An atom, like `#ok`. Atoms are plain names that only ever equal
+    /// themselves.
+    #[regex(r"#[_\p{XID_Start}]\p{XID_Continue}*", |lex| lex.slice()[1..].parse())]
+    Atom(String),
+
+    #[token("match")]
+    Match,
+
+    #[token("spawn")]
+    Spawn,
+
+    #[token("receive")]
+    Receive,
+
+    #[token("after")]
+    After,
+
+    #[token("mut")]
+    Mut,
+
+    #[token("mod")]
+    Mod,
+
+    #[token("struct")]
+    Struct,
+
+    #[token("enum")]
+    Enum,
+
+    #[token("loop")]
+    Loop,
+
+    #[token("as")]
+    As,
+
+    #[token("when")]
+    When,
+
     #[regex("(\"([^\"\\\\]|\\\\.)*\")", |lex| lex.slice()[1..lex.slice().len() - 1].parse())]
     LiteralString(String),
 
+    /// The raw contents of a backtick string, escapes and `{expr}` segments
+    /// included. The parser is in charge of splitting it into parts.
+    #[regex("`([^`\\\\]|\\\\.)*`", |lex| lex.slice()[1..lex.slice().len() - 1].parse())]
+    InterpolatedString(String),
+
+    /// A `/// ...` comment documenting the declaration that follows it.
+    #[regex(r"///([^/\n][^\n]*)?", |lex| doc_comment(lex.slice()), priority = 10)]
+    DocComment(String),
+
+    /// A `// ...` comment, running until the end of the line.
+    #[regex(r"//[^\n]*")]
+    LineComment,
+
+    /// A `/* ... */` comment. Block comments nest, so commenting out code that
+    /// already has a block comment in it works as expected.
+    #[token("/*", block_comment)]
+    BlockComment,
+
     #[token(";")]
     Semicolon,
 
@@ -18,6 +77,70 @@ pub enum Token {
     #[token("?")]
     QuestionMark,
 
+    #[token("+")]
+    Plus,
+
+    #[token("-")]
+    Minus,
+
+    #[token("*")]
+    Star,
+
+    #[token("/")]
+    Slash,
+
+    #[token("%")]
+    Percent,
+
+    #[token("==")]
+    EqualEqual,
+
+    #[token("!=")]
+    NotEqual,
+
+    #[token("<")]
+    LessThan,
+
+    #[token(">")]
+    GreaterThan,
+
+    #[token("<=")]
+    LessEqual,
+
+    #[token(">=")]
+    GreaterEqual,
+
+    #[token("&&")]
+    AndAnd,
+
+    #[token("||")]
+    OrOr,
+
+    #[token("!")]
+    Bang,
+
+    #[token("<-")]
+    LeftArrow,
+
+    #[token("=>")]
+    FatArrow,
+
+    #[token("->")]
+    Arrow,
+
+    #[token(":")]
+    Colon,
+
+    #[token(".")]
+    Dot,
+
+    #[token("|")]
+    Pipe,
+
+    /// Starts an attribute, like `@derive(Debug)`.
+    #[token("@")]
+    At,
+
     #[token(",")]
     Comma,
 
@@ -39,23 +162,174 @@ pub enum Token {
     #[token("}")]
     BraceRight,
 
-    #[regex("[0-9]+", |lex| lex.slice().parse())]
+    /// An unsigned integer literal. Negative literals are a unary minus applied
+    /// to one of these, which the parser folds back into a single literal.
+    #[regex("[0-9][0-9_]*", |lex| integer(lex.slice(), 10))]
+    #[regex("0x[0-9a-fA-F_]+", |lex| integer(&lex.slice()[2..], 16))]
+    #[regex("0o[0-7_]+", |lex| integer(&lex.slice()[2..], 8))]
+    #[regex("0b[01_]+", |lex| integer(&lex.slice()[2..], 2))]
     Number(u64),
 
-    #[regex("[0-9]*[.][0-9]+", |lex| lex.slice().parse())]
+    #[regex("([0-9][0-9_]*)?[.][0-9][0-9_]*([eE][+-]?[0-9_]+)?", |lex| float(lex.slice()))]
+    #[regex("[0-9][0-9_]*[eE][+-]?[0-9_]+", |lex| float(lex.slice()))]
     Float(f64),
 
+    /// A duration like `1000ms`, `30s` or `5m`. Letters right after the unit
+    /// make the whole run an error, so `5min` isn't read as `5m` then `in`.
+    #[regex(r"[0-9][0-9_]*(ms|s|m)\p{XID_Continue}*", |lex| duration(lex.slice()))]
+    Duration(Duration),
+
+    /// Whitespace is kept around for tools that need to reproduce the source
+    /// exactly. The parser never gets to see it.
+    #[regex(r"[ \t\r\n\f]+")]
+    Whitespace,
+
     #[error]
-    #[regex(r"[ \t\n\f]+", logos::skip)]
     Error,
 }
 
+impl Token {
+    /// Trivia are the tokens that don't change the meaning of a program:
+    /// whitespace and non-doc comments.
+    pub fn is_trivia(&self) -> bool {
+        matches!(
+            self,
+            Token::Whitespace | Token::LineComment | Token::BlockComment
+        )
+    }
+
+    /// The source text of a reserved keyword, or `None` if this token isn't one.
+    pub fn keyword(&self) -> Option<&'static str> {
+        match self {
+            Token::Match => Some("match"),
+            Token::Spawn => Some("spawn"),
+            Token::Receive => Some("receive"),
+            Token::After => Some("after"),
+            Token::Mut => Some("mut"),
+            Token::Mod => Some("mod"),
+            Token::Struct => Some("struct"),
+            Token::Enum => Some("enum"),
+            Token::Loop => Some("loop"),
+            Token::As => Some("as"),
+            Token::When => Some("when"),
+            _ => None,
+        }
+    }
+}
+
+/// Takes a trailing `!` into the identifier, unless it starts a `!=` or the
+/// identifier already ends in a `?`.
+fn identifier(lex: &mut logos::Lexer<Token>) -> String {
+    let bang = lex.remainder().starts_with('!') && !lex.remainder().starts_with("!=");
+    if bang && !lex.slice().ends_with('?') {
+        lex.bump(1);
+    }
+    lex.slice().to_string()
+}
+
+/// Parses the digits of an integer literal, skipping `_` separators. Literals
+/// that don't fit in a `u64` fail to lex and get reported as a
+/// [crate::error::LexError].
+fn integer(digits: &str, radix: u32) -> Option<u64> {
+    let digits = digits.replace('_', "");
+    u64::from_str_radix(&digits, radix).ok()
+}
+
+/// Whether `slice` is written like an integer or duration literal, even if
+/// it's too large to be one.
+pub fn is_integer_literal(slice: &str) -> bool {
+    let (digits, radix) = match slice.get(..2) {
+        Some("0x") => (&slice[2..], 16),
+        Some("0o") => (&slice[2..], 8),
+        Some("0b") => (&slice[2..], 2),
+        _ => {
+            let digits = ["ms", "s", "m"]
+                .iter()
+                .find_map(|unit| slice.strip_suffix(unit))
+                .unwrap_or(slice);
+            if !digits.starts_with(|c: char| c.is_ascii_digit()) {
+                return false;
+            }
+            (digits, 10)
+        }
+    };
+    digits.chars().any(|c| c != '_') && digits.chars().all(|c| c == '_' || c.is_digit(radix))
+}
+
+/// Whether `slice` is a number followed by letters that start like a
+/// duration unit but aren't one, like `5min`.
+pub fn has_unknown_duration_unit(slice: &str) -> bool {
+    let unit = slice.trim_start_matches(|c: char| c.is_ascii_digit() || c == '_');
+    slice.starts_with(|c: char| c.is_ascii_digit())
+        && unit.starts_with(['m', 's'])
+        && !["ms", "s", "m"].contains(&unit)
+}
+
+fn float(slice: &str) -> Option<f64> {
+    slice.replace('_', "").parse().ok()
+}
+
+fn duration(slice: &str) -> Option<Duration> {
+    let (digits, millis_per_unit) = if let Some(digits) = slice.strip_suffix("ms") {
+        (digits, 1)
+    } else if let Some(digits) = slice.strip_suffix('s') {
+        (digits, 1_000)
+    } else {
+        (slice.strip_suffix('m')?, 60_000)
+    };
+    let millis = integer(digits, 10)?.checked_mul(millis_per_unit)?;
+    Some(Duration::from_millis(millis))
+}
+
+fn doc_comment(slice: &str) -> String {
+    let text = &slice[3..];
+    text.strip_prefix(' ')
+        .unwrap_or(text)
+        .trim_end()
+        .to_string()
+}
+
+fn block_comment(lex: &mut logos::Lexer<Token>) -> bool {
+    let bytes = lex.remainder().as_bytes();
+    let mut depth = 1;
+    let mut idx = 0;
+
+    while idx < bytes.len() {
+        match &bytes[idx..] {
+            [b'/', b'*', ..] => {
+                depth += 1;
+                idx += 2;
+            }
+            [b'*', b'/', ..] => {
+                depth -= 1;
+                idx += 2;
+                if depth == 0 {
+                    lex.bump(idx);
+                    return true;
+                }
+            }
+            _ => idx += 1,
+        }
+    }
+
+    // an unterminated block comment swallows the rest of the file
+    lex.bump(bytes.len());
+    false
+}
+
+#[cfg(test)]
+#[allow(clippy::approx_constant)]
 mod tests {
     use super::*;
 
+    /// Most of these tests don't care about whitespace, so we skip it.
+    fn lexer(source: &str) -> impl Iterator<Item = Token> + '_ {
+        Token::lexer(source).filter(|token| *token != Token::Whitespace)
+    }
+
     #[test]
     fn literal_empty_list() {
-        let mut lex = Token::lexer("[]");
+        let mut lex = lexer("[]");
         assert_eq!(lex.next(), Some(Token::BracketLeft));
         assert_eq!(lex.next(), Some(Token::BracketRight));
         assert_eq!(lex.next(), None);
@@ -63,7 +337,7 @@ mod tests {
 
     #[test]
     fn literal_non_empty_list() {
-        let mut lex = Token::lexer("[3.14, true]");
+        let mut lex = lexer("[3.14, true]");
         assert_eq!(lex.next(), Some(Token::BracketLeft));
         assert_eq!(lex.next(), Some(Token::Float(3.14)));
         assert_eq!(lex.next(), Some(Token::Comma));
@@ -74,21 +348,233 @@ mod tests {
 
     #[test]
     fn literal_string() {
-        let mut lex = Token::lexer("\"3.14\"");
+        let mut lex = lexer("\"3.14\"");
         assert_eq!(lex.next(), Some(Token::LiteralString("3.14".to_string())));
         assert_eq!(lex.next(), None);
     }
 
+    #[test]
+    fn literal_interpolated_string() {
+        let mut lex = lexer(r"`hello {name}, \`quoted\``");
+        assert_eq!(
+            lex.next(),
+            Some(Token::InterpolatedString(
+                r"hello {name}, \`quoted\`".to_string()
+            ))
+        );
+        assert_eq!(lex.next(), None);
+    }
+
+    #[test]
+    fn comments() {
+        let mut lex = lexer(
+            r#"
+            // a line comment
+            /// some docs
+            ///more docs
+            //// not docs
+            /* a /* nested */ block comment */ PI
+        "#,
+        );
+        assert_eq!(lex.next(), Some(Token::LineComment));
+        assert_eq!(lex.next(), Some(Token::DocComment("some docs".to_string())));
+        assert_eq!(lex.next(), Some(Token::DocComment("more docs".to_string())));
+        assert_eq!(lex.next(), Some(Token::LineComment));
+        assert_eq!(lex.next(), Some(Token::BlockComment));
+        assert_eq!(lex.next(), Some(Token::Id("PI".to_string())));
+        assert_eq!(lex.next(), None);
+    }
+
+    #[test]
+    fn nested_block_comment() {
+        let mut lex = Token::lexer("/* a /* nested */ block comment */ PI");
+        assert_eq!(lex.next(), Some(Token::BlockComment));
+        assert_eq!(lex.slice(), "/* a /* nested */ block comment */");
+        assert_eq!(lex.next(), Some(Token::Whitespace));
+        assert_eq!(lex.next(), Some(Token::Id("PI".to_string())));
+        assert_eq!(lex.next(), None);
+    }
+
+    #[test]
+    fn unterminated_block_comment() {
+        let mut lex = lexer("/* a /* nested */ comment");
+        assert_eq!(lex.next(), Some(Token::Error));
+        assert_eq!(lex.next(), None);
+    }
+
+    #[test]
+    fn identifiers() {
+        let mut lex = lexer("foo2 user_v1 _ _private café 名前 valid? save! a1!");
+        assert_eq!(lex.next(), Some(Token::Id("foo2".to_string())));
+        assert_eq!(lex.next(), Some(Token::Id("user_v1".to_string())));
+        assert_eq!(lex.next(), Some(Token::Id("_".to_string())));
+        assert_eq!(lex.next(), Some(Token::Id("_private".to_string())));
+        assert_eq!(lex.next(), Some(Token::Id("café".to_string())));
+        assert_eq!(lex.next(), Some(Token::Id("名前".to_string())));
+        assert_eq!(lex.next(), Some(Token::Id("valid?".to_string())));
+        assert_eq!(lex.next(), Some(Token::Id("save!".to_string())));
+        assert_eq!(lex.next(), Some(Token::Id("a1!".to_string())));
+        assert_eq!(lex.next(), None);
+    }
+
+    #[test]
+    fn identifiers_leave_the_bang_of_not_equal_alone() {
+        let mut lex = lexer("a!=b save!(x) valid?!");
+        assert_eq!(lex.next(), Some(Token::Id("a".to_string())));
+        assert_eq!(lex.next(), Some(Token::NotEqual));
+        assert_eq!(lex.next(), Some(Token::Id("b".to_string())));
+        assert_eq!(lex.next(), Some(Token::Id("save!".to_string())));
+        assert_eq!(lex.next(), Some(Token::ParensLeft));
+        assert_eq!(lex.next(), Some(Token::Id("x".to_string())));
+        assert_eq!(lex.next(), Some(Token::ParensRight));
+        assert_eq!(lex.next(), Some(Token::Id("valid?".to_string())));
+        assert_eq!(lex.next(), Some(Token::Bang));
+        assert_eq!(lex.next(), None);
+    }
+
+    #[test]
+    fn identifiers_can_not_start_with_a_digit() {
+        let mut lex = lexer("2fast");
+        assert_eq!(lex.next(), Some(Token::Number(2)));
+        assert_eq!(lex.next(), Some(Token::Id("fast".to_string())));
+        assert_eq!(lex.next(), None);
+    }
+
+    #[test]
+    fn atoms() {
+        let mut lex = lexer("#ok #not_found #café # ok");
+        assert_eq!(lex.next(), Some(Token::Atom("ok".to_string())));
+        assert_eq!(lex.next(), Some(Token::Atom("not_found".to_string())));
+        assert_eq!(lex.next(), Some(Token::Atom("café".to_string())));
+        assert_eq!(lex.next(), Some(Token::Error));
+        assert_eq!(lex.next(), Some(Token::Id("ok".to_string())));
+        assert_eq!(lex.next(), None);
+    }
+
+    #[test]
+    fn keywords() {
+        let mut lex = lexer("match spawn receive after mut mod struct enum loop as when matches");
+        assert_eq!(lex.next(), Some(Token::Match));
+        assert_eq!(lex.next(), Some(Token::Spawn));
+        assert_eq!(lex.next(), Some(Token::Receive));
+        assert_eq!(lex.next(), Some(Token::After));
+        assert_eq!(lex.next(), Some(Token::Mut));
+        assert_eq!(lex.next(), Some(Token::Mod));
+        assert_eq!(lex.next(), Some(Token::Struct));
+        assert_eq!(lex.next(), Some(Token::Enum));
+        assert_eq!(lex.next(), Some(Token::Loop));
+        assert_eq!(lex.next(), Some(Token::As));
+        assert_eq!(lex.next(), Some(Token::When));
+        assert_eq!(lex.next(), Some(Token::Id("matches".to_string())));
+        assert_eq!(lex.next(), None);
+    }
+
+    #[test]
+    fn operators() {
+        let mut lex = lexer("+ - * / % == != < > <= >= && || ! <- => -> : . | @ =");
+        assert_eq!(lex.next(), Some(Token::Plus));
+        assert_eq!(lex.next(), Some(Token::Minus));
+        assert_eq!(lex.next(), Some(Token::Star));
+        assert_eq!(lex.next(), Some(Token::Slash));
+        assert_eq!(lex.next(), Some(Token::Percent));
+        assert_eq!(lex.next(), Some(Token::EqualEqual));
+        assert_eq!(lex.next(), Some(Token::NotEqual));
+        assert_eq!(lex.next(), Some(Token::LessThan));
+        assert_eq!(lex.next(), Some(Token::GreaterThan));
+        assert_eq!(lex.next(), Some(Token::LessEqual));
+        assert_eq!(lex.next(), Some(Token::GreaterEqual));
+        assert_eq!(lex.next(), Some(Token::AndAnd));
+        assert_eq!(lex.next(), Some(Token::OrOr));
+        assert_eq!(lex.next(), Some(Token::Bang));
+        assert_eq!(lex.next(), Some(Token::LeftArrow));
+        assert_eq!(lex.next(), Some(Token::FatArrow));
+        assert_eq!(lex.next(), Some(Token::Arrow));
+        assert_eq!(lex.next(), Some(Token::Colon));
+        assert_eq!(lex.next(), Some(Token::Dot));
+        assert_eq!(lex.next(), Some(Token::Pipe));
+        assert_eq!(lex.next(), Some(Token::At));
+        assert_eq!(lex.next(), Some(Token::Equal));
+        assert_eq!(lex.next(), None);
+    }
+
     #[test]
     fn literal_float() {
-        let mut lex = Token::lexer("3.14");
+        let mut lex = lexer("3.14");
         assert_eq!(lex.next(), Some(Token::Float(3.14)));
         assert_eq!(lex.next(), None);
     }
 
+    #[test]
+    fn literal_integers() {
+        let mut lex = lexer("0 1_000_000 0xFF 0o17 0b1010_1010");
+        assert_eq!(lex.next(), Some(Token::Number(0)));
+        assert_eq!(lex.next(), Some(Token::Number(1_000_000)));
+        assert_eq!(lex.next(), Some(Token::Number(0xFF)));
+        assert_eq!(lex.next(), Some(Token::Number(0o17)));
+        assert_eq!(lex.next(), Some(Token::Number(0b1010_1010)));
+        assert_eq!(lex.next(), None);
+    }
+
+    #[test]
+    fn literal_integer_overflow() {
+        let mut lex = lexer("18446744073709551616 18446744073709551615");
+        assert_eq!(lex.next(), Some(Token::Error));
+        assert_eq!(lex.next(), Some(Token::Number(u64::MAX)));
+        assert_eq!(lex.next(), None);
+    }
+
+    #[test]
+    fn integer_literals_are_recognised_even_when_they_overflow() {
+        for literal in [
+            "18446744073709551616",
+            "0xFFFF_FFFF_FFFF_FFFF_F",
+            "0b1_0",
+            "99999999999999999999ms",
+        ] {
+            assert!(is_integer_literal(literal), "{}", literal);
+        }
+        for literal in ["0x_", "0b2", "_1", "1e", "1.5", "1h"] {
+            assert!(!is_integer_literal(literal), "{}", literal);
+        }
+    }
+
+    #[test]
+    fn literal_floats_with_exponents() {
+        let mut lex = lexer("1e3 2.5E-2 1_000.5 .5");
+        assert_eq!(lex.next(), Some(Token::Float(1e3)));
+        assert_eq!(lex.next(), Some(Token::Float(2.5e-2)));
+        assert_eq!(lex.next(), Some(Token::Float(1_000.5)));
+        assert_eq!(lex.next(), Some(Token::Float(0.5)));
+        assert_eq!(lex.next(), None);
+    }
+
+    #[test]
+    fn literal_durations() {
+        let mut lex = lexer("1000ms 30s 5m 10 s");
+        assert_eq!(
+            lex.next(),
+            Some(Token::Duration(Duration::from_millis(1000)))
+        );
+        assert_eq!(lex.next(), Some(Token::Duration(Duration::from_secs(30))));
+        assert_eq!(lex.next(), Some(Token::Duration(Duration::from_secs(300))));
+        assert_eq!(lex.next(), Some(Token::Number(10)));
+        assert_eq!(lex.next(), Some(Token::Id("s".to_string())));
+        assert_eq!(lex.next(), None);
+    }
+
+    #[test]
+    fn literal_durations_with_unknown_units() {
+        let mut lex = lexer("5min 2secs 1ms_ 3m");
+        assert_eq!(lex.next(), Some(Token::Error));
+        assert_eq!(lex.next(), Some(Token::Error));
+        assert_eq!(lex.next(), Some(Token::Error));
+        assert_eq!(lex.next(), Some(Token::Duration(Duration::from_secs(180))));
+        assert_eq!(lex.next(), None);
+    }
+
     #[test]
     fn constant_definition() {
-        let mut lex = Token::lexer(
+        let mut lex = lexer(
             r#"
             PI = 3.14
             Name = "Q"
@@ -106,7 +592,7 @@ mod tests {
 
     #[test]
     fn function_definition() {
-        let mut lex = Token::lexer(
+        let mut lex = lexer(
             r#"
             hello_world =
                 () { };
@@ -130,7 +616,7 @@ mod tests {
 
     #[test]
     fn function_call() {
-        let mut lex = Token::lexer("hello_world()");
+        let mut lex = lexer("hello_world()");
         assert_eq!(lex.next(), Some(Token::Id("hello_world".to_string())));
         assert_eq!(lex.next(), Some(Token::ParensLeft));
         assert_eq!(lex.next(), Some(Token::ParensRight));